@@ -0,0 +1,67 @@
+//! Compares the bitboard with `GameGrid` on the work the CPU does for every placement it
+//! looks at. Run with `cargo bench`.
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use puyo_clone::{
+    cpu::{Cpu, Level},
+    sim::{
+        bag::Bag,
+        bitboard::BitBoard,
+        board::{Board, GRID_HEIGHT, GRID_WIDTH},
+        grid::{GameGrid, Grid},
+        nuisance::NuisanceQueue,
+        piece::PieceColor,
+    },
+};
+
+/// A half-built stack, with groups about to pop.
+fn grid() -> GameGrid {
+    let rows = [
+        "RRBGYN",
+        "BBGYRR",
+        "GGYRBB",
+        "YYRBGG",
+        "RBGY  ",
+        "R     ",
+    ];
+    let mut grid = Grid::new(GRID_HEIGHT, GRID_WIDTH, vec![None; GRID_WIDTH * GRID_HEIGHT]);
+    for (row, line) in rows.iter().enumerate() {
+        for (col, cell) in line.chars().enumerate() {
+            let color = match cell {
+                'R' => PieceColor::Red,
+                'B' => PieceColor::Blue,
+                'G' => PieceColor::Green,
+                'Y' => PieceColor::Yellow,
+                'N' => PieceColor::Nuisance,
+                _ => continue,
+            };
+            grid[[row as isize, col as isize]] = Some(color);
+        }
+    }
+    grid
+}
+
+fn groups(c: &mut Criterion) {
+    let grid = grid();
+    let bitboard = BitBoard::from(&grid);
+    c.bench_function("groups/grid", |b| b.iter(|| black_box(&grid).groups()));
+    c.bench_function("groups/bitboard", |b| b.iter(|| black_box(&bitboard).groups()));
+}
+
+fn resolve(c: &mut Criterion) {
+    let bitboard = BitBoard::from(&grid());
+    c.bench_function("resolve/bitboard", |b| b.iter(|| black_box(bitboard).resolve()));
+    let grid = grid();
+    c.bench_function("conversion", |b| {
+        b.iter(|| GameGrid::from(&BitBoard::from(black_box(&grid))))
+    });
+}
+
+fn choose(c: &mut Criterion) {
+    let mut board = Board::new(Bag::new(1, 4), NuisanceQueue::new(0));
+    board.grid = grid();
+    let cpu = Cpu::new(Level::Hard);
+    c.bench_function("choose/hard", |b| b.iter(|| cpu.choose(black_box(&board))));
+}
+
+criterion_group!(benches, groups, resolve, choose);
+criterion_main!(benches);
@@ -0,0 +1,29 @@
+//! Plays in the terminal. Only needs the simulation and crossterm, so it builds without the
+//! windowed game: `cargo run --bin tui --no-default-features`.
+use std::{env, process};
+
+use puyo_clone::{config::GameConfig, tui};
+
+fn main() {
+    let config = match GameConfig::from_args(env::args().skip(1)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{message}");
+            process::exit(1);
+        }
+    };
+    if config.bot || config.replay.is_some() {
+        eprintln!("`--bot` and `--replay` are only available in the windowed game");
+        process::exit(1);
+    }
+
+    let cpu = config.cpu.then_some(config.cpu_level);
+    let result = tui::run(&config.settings(), cpu).and_then(|replay| match &config.record {
+        Some(path) => replay.save(path),
+        None => Ok(()),
+    });
+    if let Err(message) = result {
+        eprintln!("{message}");
+        process::exit(1);
+    }
+}
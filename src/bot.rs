@@ -0,0 +1,266 @@
+//! Lets external programs play the game through line-delimited JSON.
+//!
+//! Whenever a board gets a new pair, a `state` message is written with the grid (rows from
+//! the bottom, the last one hidden above the field, `null` for empty cells), the pair, every
+//! placement it can reach, the upcoming pairs, the score and the pending nuisance. One line
+//! is then read back, either a placement:
+//!
+//! `{"placement": {"col": 2, "orientation": "ba_horizontal"}}`
+//!
+//! where `col` is the column of the first piece and the orientation where the second one is
+//! (`ab_vertical` below, `ab_horizontal` right, `ba_vertical` above, `ba_horizontal` left),
+//! or the buttons held on each of the next frames:
+//!
+//! `{"inputs": [["left"], [], ["cw", "down"]]}`
+//!
+//! with `left`, `right`, `down`, `cw`, `ccw` and `drop`, which puts the pair where it lands.
+//! Once the inputs run out no button is held until the pair lands. A `game_over` message is
+//! written when a board tops out, its `loser` is `null` when boards top out together.
+use std::io::{BufRead, Write};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{
+    board::{Board, FallingPair},
+    game::{Game, Outcome, Settings},
+    input::PlayerInput,
+    piece::{PairOrientation, PieceColor},
+    placement::{moves, Move, Placement},
+    replay::Replay,
+};
+
+#[derive(Serialize)]
+struct PairState {
+    colors: [PieceColor; 2],
+    col: isize,
+    row: isize,
+    orientation: PairOrientation,
+}
+
+#[derive(Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Message {
+    State {
+        player: usize,
+        frame: u64,
+        grid: Vec<Vec<Option<PieceColor>>>,
+        pair: PairState,
+        placements: Vec<Placement>,
+        next: Vec<[PieceColor; 2]>,
+        score: u64,
+        pending_nuisance: usize,
+    },
+    GameOver {
+        loser: Option<usize>,
+        scores: Vec<u64>,
+    },
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Button {
+    Left,
+    Right,
+    Down,
+    Cw,
+    Ccw,
+    Drop,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum Response {
+    Placement(Placement),
+    Inputs(Vec<Vec<Button>>),
+}
+
+fn to_input(buttons: &[Button]) -> PlayerInput {
+    let mut input = PlayerInput::default();
+    for button in buttons {
+        match button {
+            Button::Left => input.left = true,
+            Button::Right => input.right = true,
+            Button::Down => input.down = true,
+            Button::Cw => input.rotate_clockwise = true,
+            Button::Ccw => input.rotate_counter_clockwise = true,
+            Button::Drop => input.hard_drop = true,
+        }
+    }
+    input
+}
+
+/// What a bot decided to do with its current pair.
+enum Plan {
+    Placement(Placement),
+    Inputs(std::vec::IntoIter<PlayerInput>),
+}
+
+impl Plan {
+    fn next_input(&mut self, falling_pair: &FallingPair, previous: PlayerInput) -> PlayerInput {
+        match self {
+            Plan::Placement(placement) => placement.input(falling_pair, previous),
+            Plan::Inputs(inputs) => inputs.next().unwrap_or_default(),
+        }
+    }
+}
+
+fn state_message(
+    player: usize,
+    frame: u64,
+    board: &Board,
+    falling_pair: &FallingPair,
+    moves: &[Move],
+) -> Message {
+    let grid = (0..board.grid.height as isize)
+        .map(|row| (0..board.grid.width as isize).map(|col| board.grid[[row, col]]).collect())
+        .collect();
+
+    Message::State {
+        player,
+        frame,
+        grid,
+        pair: PairState {
+            colors: falling_pair.colors,
+            col: falling_pair.position.col(),
+            row: falling_pair.position.row(),
+            orientation: falling_pair.pair.orientation(),
+        },
+        placements: moves.iter().map(|m| m.placement).collect(),
+        next: board.bag.preview().copied().collect(),
+        score: board.score.value,
+        pending_nuisance: board.nuisance.pending,
+    }
+}
+
+fn send(writer: &mut impl Write, message: &Message) -> Result<(), String> {
+    let line = serde_json::to_string(message).map_err(|error| error.to_string())?;
+    writeln!(writer, "{line}")
+        .and_then(|_| writer.flush())
+        .map_err(|error| format!("could not write the board state: {error}"))
+}
+
+/// The plan of the bot for the current pair of `board`, a placement is played with the
+/// inputs of its move among `moves`.
+fn receive(reader: &mut impl BufRead, board: &Board, moves: Vec<Move>) -> Result<Plan, String> {
+    let mut line = String::new();
+    let read = reader
+        .read_line(&mut line)
+        .map_err(|error| format!("could not read the bot response: {error}"))?;
+    if read == 0 {
+        return Err("the bot closed its input".to_string());
+    }
+
+    let response: Response = serde_json::from_str(&line)
+        .map_err(|error| format!("invalid bot response `{}`: {error}", line.trim()))?;
+    match response {
+        Response::Placement(placement) if !(0..board.grid.width as isize).contains(&placement.col) => {
+            Err(format!("invalid placement column {}", placement.col))
+        }
+        Response::Placement(placement) => {
+            let m = moves.into_iter().find(|m| m.placement == placement);
+            Ok(m.map_or(Plan::Placement(placement), |m| Plan::Inputs(m.inputs.into_iter())))
+        }
+        Response::Inputs(frames) => Ok(Plan::Inputs(
+            frames.iter().map(|buttons| to_input(buttons)).collect::<Vec<_>>().into_iter(),
+        )),
+    }
+}
+
+/// Plays a whole game, every player asking the bot on the other end of `reader` and
+/// `writer` for its moves. Returns the replay of the game.
+pub fn run(settings: &Settings, mut reader: impl BufRead, mut writer: impl Write) -> Result<Replay, String> {
+    let mut game = Game::new(settings);
+    let mut replay = Replay::new(settings.clone());
+    let mut plans: Vec<Option<Plan>> = (0..settings.players).map(|_| None).collect();
+    let mut inputs = vec![PlayerInput::default(); settings.players];
+
+    while game.outcome().is_none() {
+        for (player, board) in game.boards.iter().enumerate() {
+            let Some(falling_pair) = board.pair.as_ref() else { continue };
+
+            if plans[player].is_none() {
+                let moves = moves(board);
+                send(&mut writer, &state_message(player, game.frame, board, falling_pair, &moves))?;
+                plans[player] = Some(receive(&mut reader, board, moves)?);
+            }
+            if let Some(plan) = plans[player].as_mut() {
+                inputs[player] = plan.next_input(falling_pair, inputs[player]);
+            }
+        }
+
+        replay.record_inputs(game.frame, &inputs);
+        let events = game.step(&inputs);
+        for (plan, board_events) in plans.iter_mut().zip(events) {
+            if board_events.pair_landed {
+                *plan = None;
+            }
+        }
+    }
+
+    let loser = match game.outcome() {
+        Some(Outcome::Loser(loser)) => Some(loser),
+        _ => None,
+    };
+    let scores = game.boards.iter().map(|board| board.score.value).collect();
+    send(&mut writer, &Message::GameOver { loser, scores })?;
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::board::{GRID_WIDTH, STARTING_COL};
+
+    fn settings(players: usize) -> Settings {
+        Settings { seed: 3, players, ..Default::default() }
+    }
+
+    fn messages(output: &[u8]) -> Vec<serde_json::Value> {
+        output
+            .split(|&byte| byte == b'\n')
+            .filter(|line| !line.is_empty())
+            .map(|line| serde_json::from_slice(line).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_placements() {
+        // one pair in the corner, then stack the others on the spawn until the game is over
+        let corner = r#"{"placement": {"col": 0, "orientation": "ba_vertical"}}"#;
+        let spawn = r#"{"placement": {"col": 5, "orientation": "ab_vertical"}}"#;
+        let responses = format!("{corner}\n{}", format!("{spawn}\n").repeat(20));
+        let mut output = vec![];
+        run(&settings(1), responses.as_bytes(), &mut output).unwrap();
+
+        let messages = messages(&output);
+        let state = &messages[0];
+        assert!(state["type"] == "state");
+        assert!(state["pair"]["col"] == STARTING_COL);
+        assert!(state["pair"]["orientation"] == "ab_vertical");
+        assert!(state["grid"][0].as_array().unwrap().len() == GRID_WIDTH);
+        assert!(state["next"].as_array().unwrap().len() == 2);
+        assert!(state["placements"].as_array().unwrap().len() == 4 * GRID_WIDTH - 2);
+
+        // the second pair sees the first one in the corner
+        let grid = &messages[1]["grid"];
+        assert!(grid[0][0] == messages[0]["pair"]["colors"][0]);
+        assert!(grid[1][0] == messages[0]["pair"]["colors"][1]);
+        assert!(messages.last().unwrap()["type"] == "game_over");
+    }
+
+    #[test]
+    fn test_inputs() {
+        let responses = r#"{"inputs": [[], ["down"], ["down"]]}"#.to_string() + "\n";
+        let mut output = vec![];
+        let replay = run(&settings(2), responses.repeat(40).as_bytes(), &mut output).unwrap();
+
+        let messages = messages(&output);
+        assert!(messages[0]["player"] == 0);
+        assert!(messages[1]["player"] == 1);
+        assert!(messages.last().unwrap()["type"] == "game_over");
+        assert!(replay.entries.len() > 2);
+
+        assert!(run(&settings(1), "{\"col\": 3}\n".as_bytes(), vec![]).is_err());
+        assert!(run(&settings(1), "".as_bytes(), vec![]).is_err());
+    }
+}
@@ -0,0 +1,170 @@
+use std::path::PathBuf;
+
+use crate::{cpu::Level, sim::game::Settings};
+
+pub use crate::sim::bag::{MAX_COLORS, MIN_COLORS};
+
+const USAGE: &str = "usage: puyo_clone [--seed <u64>] [--classic] [--colors <3-6>] [--versus] \
+                     [--cpu <easy|medium|hard>] [--record <file>] [--replay <file>] [--bot] \
+                     [--keys <file>] [--lock-delay <frames>]";
+
+const DEFAULT_KEYS: &str = "keys.txt";
+
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "gui", derive(bevy::prelude::Resource))]
+pub struct GameConfig {
+    /// Seed of the piece generator, the color sequence depends only on it.
+    pub seed: u64,
+    /// Use a pre-generated looping sequence of pairs instead of an endless random one.
+    pub classic_sequence: bool,
+    /// Number of piece colors in play, between `MIN_COLORS` and `MAX_COLORS`.
+    pub n_colors: usize,
+    /// Number of boards, two players share the keyboard in versus.
+    pub players: usize,
+    /// Frames a pair resting on the stack can still be moved before it locks.
+    pub lock_delay: u32,
+    /// Whether the second player is played by the computer.
+    pub cpu: bool,
+    pub cpu_level: Level,
+    /// Where to write the replay of the game being played.
+    pub record: Option<PathBuf>,
+    /// Replay file to play back instead of reading the keyboard.
+    pub replay: Option<PathBuf>,
+    /// Play headless, with the moves read from stdin as JSON and the boards written to stdout.
+    pub bot: bool,
+    /// Key bindings file, read at startup and written by the rebinding screen. The terminal
+    /// game keeps its own keys.
+    pub keys: PathBuf,
+}
+
+impl Default for GameConfig {
+    fn default() -> Self {
+        let settings = Settings::default();
+        Self {
+            seed: rand::random(),
+            classic_sequence: settings.classic_sequence,
+            n_colors: settings.n_colors,
+            players: settings.players,
+            lock_delay: settings.lock_delay,
+            cpu: false,
+            cpu_level: Level::default(),
+            record: None,
+            replay: None,
+            bot: false,
+            keys: PathBuf::from(DEFAULT_KEYS),
+        }
+    }
+}
+
+impl GameConfig {
+    pub fn settings(&self) -> Settings {
+        Settings {
+            seed: self.seed,
+            classic_sequence: self.classic_sequence,
+            n_colors: self.n_colors,
+            players: self.players,
+            lock_delay: self.lock_delay,
+        }
+    }
+
+    /// Plays with the settings of a replay.
+    pub fn apply_settings(&mut self, settings: &Settings) {
+        self.seed = settings.seed;
+        self.classic_sequence = settings.classic_sequence;
+        self.n_colors = settings.n_colors;
+        self.players = settings.players;
+        self.lock_delay = settings.lock_delay;
+    }
+
+    pub fn from_args(args: impl IntoIterator<Item = String>) -> Result<Self, String> {
+        let mut config = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--seed" => {
+                    let value = args.next().ok_or(USAGE)?;
+                    config.seed = value
+                        .parse()
+                        .map_err(|_| format!("invalid seed `{value}`\n{USAGE}"))?;
+                }
+                "--classic" => config.classic_sequence = true,
+                "--versus" => config.players = 2,
+                "--cpu" => {
+                    let value = args.next().ok_or(USAGE)?;
+                    config.cpu_level = value.parse().map_err(|error| format!("{error}\n{USAGE}"))?;
+                    config.cpu = true;
+                    config.players = 2;
+                }
+                "--record" => config.record = Some(args.next().ok_or(USAGE)?.into()),
+                "--replay" => config.replay = Some(args.next().ok_or(USAGE)?.into()),
+                "--bot" => config.bot = true,
+                "--keys" => config.keys = args.next().ok_or(USAGE)?.into(),
+                "--lock-delay" => {
+                    let value = args.next().ok_or(USAGE)?;
+                    config.lock_delay = value
+                        .parse()
+                        .map_err(|_| format!("invalid lock delay `{value}`\n{USAGE}"))?;
+                }
+                "--colors" => {
+                    let value = args.next().ok_or(USAGE)?;
+                    config.n_colors = value
+                        .parse()
+                        .ok()
+                        .filter(|n_colors| (MIN_COLORS..=MAX_COLORS).contains(n_colors))
+                        .ok_or(format!("invalid number of colors `{value}`\n{USAGE}"))?;
+                }
+                _ => return Err(format!("unknown argument `{arg}`\n{USAGE}")),
+            }
+        }
+
+        Ok(config)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::board::LOCK_DELAY;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn test_from_args() {
+        let config =
+            GameConfig::from_args(args(&["--seed", "42", "--classic", "--colors", "5", "--versus"]))
+                .unwrap();
+        let replay = GameConfig::from_args(args(&["--replay", "game.replay"])).unwrap();
+        let bot = GameConfig::from_args(args(&["--bot", "--versus"])).unwrap();
+        let cpu = GameConfig::from_args(args(&["--cpu", "hard"])).unwrap();
+        let lock = GameConfig::from_args(args(&["--lock-delay", "0"])).unwrap();
+
+        assert!(config.seed == 42);
+        assert!(config.classic_sequence);
+        assert!(config.n_colors == 5);
+        assert!(config.players == 2);
+        assert!(replay.replay == Some(PathBuf::from("game.replay")));
+        assert!(replay.record.is_none());
+        assert!(bot.bot && !config.bot);
+        assert!(cpu.cpu && cpu.cpu_level == Level::Hard && cpu.players == 2);
+        assert!(config.keys.as_os_str() == DEFAULT_KEYS);
+        assert!(lock.settings().lock_delay == 0);
+        assert!(config.lock_delay == LOCK_DELAY);
+    }
+
+    #[test]
+    fn test_invalid_args() {
+        assert!(GameConfig::from_args(args(&["--seed"])).is_err());
+        assert!(GameConfig::from_args(args(&["--seed", "red"])).is_err());
+        assert!(GameConfig::from_args(args(&["--colors", "2"])).is_err());
+        assert!(GameConfig::from_args(args(&["--colors", "7"])).is_err());
+        assert!(GameConfig::from_args(args(&["--speed"])).is_err());
+        assert!(GameConfig::from_args(args(&["--record"])).is_err());
+        assert!(GameConfig::from_args(args(&["--keys"])).is_err());
+        assert!(GameConfig::from_args(args(&["--lock-delay", "-1"])).is_err());
+        assert!(GameConfig::from_args(args(&["--cpu", "impossible"])).is_err());
+    }
+}
@@ -0,0 +1,298 @@
+//! A computer player. It searches the placements of its current pair, and of the upcoming
+//! ones on the higher levels, and drops the pair where the board ends up best.
+use std::{fmt, str::FromStr, vec};
+
+use crate::sim::{
+    bitboard::BitBoard,
+    board::{
+        spawn_cells, Board, BoardEvents, GRID_WIDTH, MIN_GROUP_SIZE, STARTING_COL, STARTING_ROW,
+    },
+    grid::GameGrid,
+    input::PlayerInput,
+    piece::PieceColor,
+    placement::{moves, Move, Placement},
+    score::ALL_CLEAR_BONUS,
+};
+
+/// Placements kept at each step of the search before looking at the next pair.
+const BEAM_WIDTH: usize = 6;
+
+/// Value of a board where the longest chain one more piece could start has `n` links:
+/// `CHAIN_WEIGHT * n * n`.
+const CHAIN_WEIGHT: i64 = 60;
+/// Value of a group of `n` pieces too small to pop: `CONNECTION_WEIGHT * n * n`.
+const CONNECTION_WEIGHT: i64 = 4;
+const HEIGHT_WEIGHT: i64 = 2;
+/// Rows below the spawn from which a stack gets dangerous.
+const DANGER_ROWS: isize = 6;
+const DANGER_WEIGHT: i64 = 400;
+const LOST: i64 = i64::MIN / 2;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Level {
+    Easy,
+    #[default]
+    Medium,
+    Hard,
+}
+
+impl Level {
+    /// Pairs looked at for every placement, the current one included.
+    fn depth(self) -> usize {
+        match self {
+            Level::Easy => 1,
+            Level::Medium => 2,
+            Level::Hard => 3,
+        }
+    }
+
+    /// Frames spent looking at a new pair before moving it.
+    fn reaction_delay(self) -> u32 {
+        match self {
+            Level::Easy => 40,
+            Level::Medium => 16,
+            Level::Hard => 4,
+        }
+    }
+
+    /// The level after this one, back to easy after hard.
+    pub fn next(self) -> Self {
+        match self {
+            Level::Easy => Level::Medium,
+            Level::Medium => Level::Hard,
+            Level::Hard => Level::Easy,
+        }
+    }
+}
+
+impl fmt::Display for Level {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Level::Easy => "easy",
+            Level::Medium => "medium",
+            Level::Hard => "hard",
+        };
+        f.write_str(name)
+    }
+}
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        match s {
+            "easy" => Ok(Level::Easy),
+            "medium" => Ok(Level::Medium),
+            "hard" => Ok(Level::Hard),
+            _ => Err(format!("unknown level `{s}`")),
+        }
+    }
+}
+
+/// The longest chain a single piece dropped on the grid starts.
+fn chain_potential(bitboard: &BitBoard) -> usize {
+    let mut longest = 0;
+    for (col, row) in bitboard.column_heights().into_iter().enumerate() {
+        if row >= bitboard.height() {
+            continue;
+        }
+
+        // only the colors next to where the piece lands can connect with it
+        let neighbours = [(row.wrapping_sub(1), col), (row, col.wrapping_sub(1)), (row, col + 1)];
+        let mut colors: Vec<PieceColor> = neighbours
+            .into_iter()
+            .filter(|&(row, col)| row < bitboard.height() && col < GRID_WIDTH)
+            .filter_map(|(row, col)| bitboard.get(row, col))
+            .filter(|&color| color != PieceColor::Nuisance)
+            .collect();
+        colors.sort_unstable();
+        colors.dedup();
+
+        for color in colors {
+            let mut next = *bitboard;
+            next.set(row, col, Some(color));
+            if next.group_size(row, col) as usize >= MIN_GROUP_SIZE {
+                longest = longest.max(next.resolve().len());
+            }
+        }
+    }
+    longest
+}
+
+/// How good a settled grid is to keep building on.
+fn evaluate(bitboard: &BitBoard) -> i64 {
+    let chain = chain_potential(bitboard) as i64;
+    let connections: i64 = bitboard.groups().iter().map(|&(_, size)| (size * size) as i64).sum();
+    let column_heights = bitboard.column_heights();
+    let heights: i64 = column_heights.iter().map(|&height| height as i64).sum();
+    let spawn_height = column_heights[STARTING_COL as usize] as isize;
+    let danger = (spawn_height - (STARTING_ROW - DANGER_ROWS)).max(0) as i64;
+
+    CHAIN_WEIGHT * chain * chain + CONNECTION_WEIGHT * connections
+        - HEIGHT_WEIGHT * heights
+        - DANGER_WEIGHT * danger * danger
+}
+
+/// The best of `placements` for the first of `pairs` and its value, looking at the others
+/// after it, which can go wherever `Placement::reachable` says. None when every placement
+/// tops out.
+fn search(
+    bitboard: &BitBoard,
+    pairs: &[[PieceColor; 2]],
+    placements: Vec<Placement>,
+) -> Option<(Placement, i64)> {
+    let mut candidates: Vec<(Placement, BitBoard, i64, i64)> = vec![];
+    for placement in placements {
+        let mut next = *bitboard;
+        let fits = placement
+            .pieces(pairs[0])
+            .into_iter()
+            .all(|(col, color)| next.drop_piece(col as usize, color));
+        if !fits {
+            continue;
+        }
+        let mut points = next.resolve().iter().sum::<u64>() as i64;
+        if points > 0 && next.is_clear() {
+            points += ALL_CLEAR_BONUS as i64;
+        }
+        let topped_out = spawn_cells()
+            .into_iter()
+            .any(|cell| next.get(cell.row() as usize, cell.col() as usize).is_some());
+        if topped_out {
+            continue;
+        }
+        let value = points + evaluate(&next);
+        candidates.push((placement, next, points, value));
+    }
+
+    if pairs.len() > 1 {
+        candidates.sort_by_key(|&(_, _, _, value)| -value);
+        candidates.truncate(BEAM_WIDTH);
+        for (_, next, points, value) in candidates.iter_mut() {
+            let placements = Placement::reachable(&GameGrid::from(&*next));
+            *value = match search(next, &pairs[1..], placements) {
+                Some((_, next_value)) => *points + next_value,
+                None => LOST,
+            };
+        }
+    }
+
+    // the first of the best placements, so that ties are broken the same way every time
+    let best = candidates.iter().map(|&(_, _, _, value)| value).max()?;
+    candidates
+        .into_iter()
+        .find(|&(_, _, _, value)| value == best)
+        .map(|(placement, _, _, value)| (placement, value))
+}
+
+/// Plays a board on its own. Feed it the board before every step and the events after.
+#[derive(Clone)]
+pub struct Cpu {
+    level: Level,
+    /// Buttons left to hold to play the chosen move.
+    inputs: Option<vec::IntoIter<PlayerInput>>,
+    delay: u32,
+}
+
+impl Cpu {
+    pub fn new(level: Level) -> Self {
+        Self {
+            level,
+            inputs: None,
+            delay: level.reaction_delay(),
+        }
+    }
+
+    /// Forgets the current pair, for when the board is reset.
+    pub fn reset(&mut self) {
+        *self = Self::new(self.level);
+    }
+
+    /// Where the current pair of `board` should go, among the moves it can make. The first
+    /// one when every move tops out, None without a pair.
+    pub fn choose(&self, board: &Board) -> Option<Move> {
+        let falling_pair = board.pair.as_ref()?;
+        let mut moves = moves(board);
+
+        let pairs: Vec<[PieceColor; 2]> = [falling_pair.colors]
+            .into_iter()
+            .chain(board.bag.preview().copied())
+            .take(self.level.depth())
+            .collect();
+        // pairs only spawn once everything has landed and popped
+        let placements = moves.iter().map(|m| m.placement).collect();
+        let best = search(&BitBoard::from(&board.grid), &pairs, placements);
+        let index = best.and_then(|(placement, _)| moves.iter().position(|m| m.placement == placement));
+        if moves.is_empty() {
+            None
+        } else {
+            Some(moves.swap_remove(index.unwrap_or(0)))
+        }
+    }
+
+    /// Buttons held on the next step of `board`.
+    pub fn input(&mut self, board: &Board) -> PlayerInput {
+        if board.pair.is_none() {
+            return PlayerInput::default();
+        }
+        if self.delay > 0 {
+            self.delay -= 1;
+            return PlayerInput::default();
+        }
+        if self.inputs.is_none() {
+            let inputs = self.choose(board).map(|m| m.inputs).unwrap_or_default();
+            self.inputs = Some(inputs.into_iter());
+        }
+        self.inputs.as_mut().and_then(Iterator::next).unwrap_or_default()
+    }
+
+    pub fn update(&mut self, events: &BoardEvents) {
+        if events.pair_landed {
+            self.inputs = None;
+            self.delay = self.level.reaction_delay();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{
+        board::GRID_HEIGHT,
+        game::{Game, Settings},
+    };
+
+    #[test]
+    fn test_chain_potential() {
+        let mut bitboard = BitBoard::new(GRID_HEIGHT);
+        bitboard.set(0, 0, Some(PieceColor::Blue));
+        for col in 1..4 {
+            bitboard.set(0, col, Some(PieceColor::Red));
+            bitboard.set(1, col, Some(PieceColor::Blue));
+        }
+        // a red next to the others pops them, then the blues
+        assert!(chain_potential(&bitboard) == 2);
+
+        bitboard.set(0, 0, Some(PieceColor::Green));
+        assert!(chain_potential(&bitboard) == 1);
+    }
+
+    #[test]
+    fn test_plays_versus() {
+        let settings = Settings { seed: 7, players: 2, ..Default::default() };
+        let mut game = Game::new(&settings);
+        let mut cpus = [Cpu::new(Level::Hard), Cpu::new(Level::Easy)];
+
+        for _ in 0..6000 {
+            let inputs: Vec<PlayerInput> =
+                cpus.iter_mut().zip(&game.boards).map(|(cpu, board)| cpu.input(board)).collect();
+            for (cpu, events) in cpus.iter_mut().zip(game.step(&inputs)) {
+                cpu.update(&events);
+            }
+        }
+
+        assert!(game.boards.iter().all(|board| board.score.value > 0));
+        assert!("hard".parse::<Level>() == Ok(Level::Hard));
+        assert!("impossible".parse::<Level>().is_err());
+    }
+}
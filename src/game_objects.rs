@@ -1,4 +1,7 @@
-pub mod grid;
+pub mod bindings;
 pub mod piece;
-pub mod fall;
-pub mod movement;
+pub mod score;
+pub mod game_state;
+pub mod player;
+pub mod replay;
+pub mod simulation;
@@ -0,0 +1,389 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use bevy::prelude::*;
+use puyo_clone::config::GameConfig;
+
+use crate::{
+    game_objects::{
+        game_state::{spawn_overlay, GameState, Overlay},
+        player::Player,
+    },
+};
+
+const HEADER: &str = "puyo_clone keys";
+
+const BINDINGS_OVERLAY_SIZE: Vec2 = Vec2::new(560., 480.);
+const BINDINGS_FONT_SIZE: f32 = 28.;
+
+/// What a player can do, each action is bound to one key.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveLeft,
+    MoveRight,
+    SoftDrop,
+    RotateClockwise,
+    RotateCounterClockwise,
+    HardDrop,
+    Pause,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Action::MoveLeft,
+        Action::MoveRight,
+        Action::SoftDrop,
+        Action::RotateClockwise,
+        Action::RotateCounterClockwise,
+        Action::HardDrop,
+        Action::Pause,
+    ];
+
+    /// Name in the bindings file.
+    fn name(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "move_left",
+            Action::MoveRight => "move_right",
+            Action::SoftDrop => "soft_drop",
+            Action::RotateClockwise => "rotate_cw",
+            Action::RotateCounterClockwise => "rotate_ccw",
+            Action::HardDrop => "hard_drop",
+            Action::Pause => "pause",
+        }
+    }
+
+    /// Name on the rebinding screen.
+    fn label(self) -> &'static str {
+        match self {
+            Action::MoveLeft => "Move left",
+            Action::MoveRight => "Move right",
+            Action::SoftDrop => "Soft drop",
+            Action::RotateClockwise => "Rotate clockwise",
+            Action::RotateCounterClockwise => "Rotate counter-clockwise",
+            Action::HardDrop => "Hard drop",
+            Action::Pause => "Pause",
+        }
+    }
+}
+
+/// Keys that can be bound, written in the bindings file by their `KeyCode` name.
+#[rustfmt::skip]
+const KEYS: &[KeyCode] = &[
+    KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4, KeyCode::Key5,
+    KeyCode::Key6, KeyCode::Key7, KeyCode::Key8, KeyCode::Key9, KeyCode::Key0,
+    KeyCode::A, KeyCode::B, KeyCode::C, KeyCode::D, KeyCode::E, KeyCode::F, KeyCode::G,
+    KeyCode::H, KeyCode::I, KeyCode::J, KeyCode::K, KeyCode::L, KeyCode::M, KeyCode::N,
+    KeyCode::O, KeyCode::P, KeyCode::Q, KeyCode::R, KeyCode::S, KeyCode::T, KeyCode::U,
+    KeyCode::V, KeyCode::W, KeyCode::X, KeyCode::Y, KeyCode::Z,
+    KeyCode::Escape, KeyCode::F1, KeyCode::F2, KeyCode::F3, KeyCode::F4, KeyCode::F5,
+    KeyCode::F6, KeyCode::F7, KeyCode::F8, KeyCode::F9, KeyCode::F10, KeyCode::F11,
+    KeyCode::F12, KeyCode::Insert, KeyCode::Home, KeyCode::Delete, KeyCode::End,
+    KeyCode::PageDown, KeyCode::PageUp, KeyCode::Left, KeyCode::Up, KeyCode::Right,
+    KeyCode::Down, KeyCode::Back, KeyCode::Return, KeyCode::Space, KeyCode::Tab,
+    KeyCode::Numpad0, KeyCode::Numpad1, KeyCode::Numpad2, KeyCode::Numpad3,
+    KeyCode::Numpad4, KeyCode::Numpad5, KeyCode::Numpad6, KeyCode::Numpad7,
+    KeyCode::Numpad8, KeyCode::Numpad9, KeyCode::NumpadAdd, KeyCode::NumpadSubtract,
+    KeyCode::NumpadMultiply, KeyCode::NumpadDivide, KeyCode::NumpadDecimal,
+    KeyCode::NumpadEnter, KeyCode::Apostrophe, KeyCode::Backslash, KeyCode::Comma,
+    KeyCode::Equals, KeyCode::Grave, KeyCode::Minus, KeyCode::Period, KeyCode::Semicolon,
+    KeyCode::Slash, KeyCode::BracketLeft, KeyCode::BracketRight, KeyCode::AltLeft,
+    KeyCode::AltRight, KeyCode::ControlLeft, KeyCode::ControlRight, KeyCode::ShiftLeft,
+    KeyCode::ShiftRight,
+];
+
+fn key_name(key: KeyCode) -> String {
+    format!("{key:?}")
+}
+
+fn parse_key(name: &str) -> Option<KeyCode> {
+    KEYS.iter().copied().find(|&key| key_name(key) == name)
+}
+
+/// The key of every action of one player.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ActionKeys([KeyCode; Action::ALL.len()]);
+
+impl ActionKeys {
+    pub fn key(&self, action: Action) -> KeyCode {
+        self.0[action as usize]
+    }
+
+    pub fn set(&mut self, action: Action, key: KeyCode) {
+        self.0[action as usize] = key;
+    }
+
+    pub fn pressed(&self, keyboard_input: &Input<KeyCode>, action: Action) -> bool {
+        keyboard_input.pressed(self.key(action))
+    }
+
+    pub fn just_pressed(&self, keyboard_input: &Input<KeyCode>, action: Action) -> bool {
+        keyboard_input.just_pressed(self.key(action))
+    }
+}
+
+/// Ways the keyboard is shared, each one with its own keys.
+const LAYOUTS: [&str; 3] = ["single", "versus 1", "versus 2"];
+
+/// The keys of a player playing alone, then of the two players sharing the keyboard in
+/// versus. Loaded from `GameConfig::keys` and saved there by the rebinding screen.
+#[derive(Resource, Clone, Debug, PartialEq, Eq)]
+pub struct KeyBindings {
+    layouts: [ActionKeys; LAYOUTS.len()],
+}
+
+impl Default for KeyBindings {
+    fn default() -> Self {
+        use KeyCode::*;
+        Self {
+            layouts: [
+                ActionKeys([Left, Right, Down, D, A, Up, Escape]),
+                ActionKeys([A, D, S, G, F, W, Escape]),
+                ActionKeys([Left, Right, Down, Period, Comma, Up, Escape]),
+            ],
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Keys of `player` when `n_humans` share the keyboard.
+    pub fn keys(&self, player: Player, n_humans: usize) -> &ActionKeys {
+        match n_humans {
+            1 => &self.layouts[0],
+            _ => &self.layouts[1 + player.0.min(1)],
+        }
+    }
+
+    /// Checks that no key does two things at once, in a layout or between the two players
+    /// of versus, who only share the pause key.
+    fn check(&self) -> Result<(), String> {
+        for (layout, keys) in LAYOUTS.iter().zip(&self.layouts) {
+            for (index, action) in Action::ALL.into_iter().enumerate() {
+                let key = keys.key(action);
+                if let Some(other) = Action::ALL[..index].iter().find(|&&other| keys.key(other) == key) {
+                    return Err(format!(
+                        "`{}` is bound to both {} and {} in [{layout}]",
+                        key_name(key),
+                        other.name(),
+                        action.name()
+                    ));
+                }
+            }
+        }
+
+        let [_, first, second] = &self.layouts;
+        for action in Action::ALL {
+            let other = Action::ALL.into_iter().find(|&other| second.key(other) == first.key(action));
+            match other {
+                Some(Action::Pause) if action == Action::Pause => (),
+                Some(other) => {
+                    let key = key_name(first.key(action));
+                    return Err(format!(
+                        "`{key}` is bound to both {} in [{}] and {} in [{}]",
+                        action.name(),
+                        LAYOUTS[1],
+                        other.name(),
+                        LAYOUTS[2]
+                    ));
+                }
+                None => (),
+            }
+        }
+        Ok(())
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_string())
+            .map_err(|error| format!("could not write `{}`: {error}", path.as_ref().display()))
+    }
+
+    /// The bindings saved at `path`, the default ones when there is no such file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        if !path.as_ref().exists() {
+            return Ok(Self::default());
+        }
+        fs::read_to_string(path.as_ref())
+            .map_err(|error| format!("could not read `{}`: {error}", path.as_ref().display()))?
+            .parse()
+            .map_err(|error| format!("`{}`: {error}", path.as_ref().display()))
+    }
+}
+
+impl fmt::Display for KeyBindings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER}")?;
+        for (layout, keys) in LAYOUTS.iter().zip(&self.layouts) {
+            writeln!(f, "\n[{layout}]")?;
+            for action in Action::ALL {
+                writeln!(f, "{} {}", action.name(), key_name(keys.key(action)))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for KeyBindings {
+    type Err = String;
+
+    /// Actions missing from the file keep their default keys. A key bound twice is an error.
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut lines = s.lines();
+        if lines.next().map(str::trim) != Some(HEADER) {
+            return Err("not a key bindings file".to_string());
+        }
+
+        let mut bindings = KeyBindings::default();
+        let mut layout = None;
+        let lines = lines.map(str::trim).filter(|line| !line.is_empty() && !line.starts_with('#'));
+        for line in lines {
+            let invalid = || format!("invalid key bindings line `{line}`");
+
+            if let Some(name) = line.strip_prefix('[').and_then(|line| line.strip_suffix(']')) {
+                let index = LAYOUTS.iter().position(|&layout| layout == name);
+                layout = Some(index.ok_or_else(invalid)?);
+                continue;
+            }
+            let [name, key] = line.split_whitespace().collect::<Vec<_>>()[..] else {
+                return Err(invalid());
+            };
+            let action = Action::ALL.into_iter().find(|action| action.name() == name);
+            let key = parse_key(key).ok_or_else(|| format!("{}: unknown key `{key}`", invalid()))?;
+            match (layout, action) {
+                (Some(layout), Some(action)) => bindings.layouts[layout].set(action, key),
+                _ => return Err(invalid()),
+            }
+        }
+        bindings.check()?;
+        Ok(bindings)
+    }
+}
+
+/// Where the rebinding screen is at.
+#[derive(Resource, Default)]
+pub struct BindingsMenu {
+    layout: usize,
+    selected: usize,
+    /// Whether the next key pressed gets bound to the selected action.
+    waiting: bool,
+    /// Key last pressed to rebind the selected action, refused since it does something else.
+    taken: Option<KeyCode>,
+}
+
+fn bindings_menu_text(bindings: &KeyBindings, menu: &BindingsMenu) -> String {
+    let layout = ["Single player", "Versus, player 1", "Versus, player 2"][menu.layout];
+    let mut text = format!("Keys: {layout}\n");
+    for (index, action) in Action::ALL.into_iter().enumerate() {
+        let key = bindings.layouts[menu.layout].key(action);
+        let cursor = if index == menu.selected { "> " } else { "" };
+        let key = if index == menu.selected && menu.waiting {
+            match menu.taken {
+                Some(taken) => format!("press a key, {} is taken", key_name(taken)),
+                None => "press a key".to_string(),
+            }
+        } else {
+            key_name(key)
+        };
+        text += &format!("{cursor}{}: {key}\n", action.label());
+    }
+    text += "Up/Down: Select  Enter: Rebind\nTab: Next player  Esc: Back";
+    text
+}
+
+pub fn show_bindings_menu(mut commands: Commands, bindings: Res<KeyBindings>) {
+    let menu = BindingsMenu::default();
+    spawn_overlay(
+        &mut commands,
+        bindings_menu_text(&bindings, &menu),
+        BINDINGS_OVERLAY_SIZE,
+        BINDINGS_FONT_SIZE,
+    );
+    commands.insert_resource(menu);
+}
+
+pub fn bindings_menu_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut bindings: ResMut<KeyBindings>,
+    mut menu: ResMut<BindingsMenu>,
+    mut query_text: Query<&mut Text, With<Overlay>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let Some(&key) = keyboard_input.get_just_pressed().next() else { return };
+
+    let n_actions = Action::ALL.len();
+    if menu.waiting {
+        let action = Action::ALL[menu.selected];
+        // Esc cancels, unless it is bound back to pausing
+        if key == KeyCode::Escape && action != Action::Pause {
+            menu.waiting = false;
+            menu.taken = None;
+        } else if KEYS.contains(&key) {
+            let mut rebound = bindings.clone();
+            rebound.layouts[menu.layout].set(action, key);
+            if rebound.check().is_ok() {
+                *bindings = rebound;
+                menu.waiting = false;
+                menu.taken = None;
+            } else {
+                menu.taken = Some(key);
+            }
+        } else {
+            // keys that can't be written in the bindings file
+            return;
+        }
+    } else {
+        match key {
+            KeyCode::Up => menu.selected = (menu.selected + n_actions - 1) % n_actions,
+            KeyCode::Down => menu.selected = (menu.selected + 1) % n_actions,
+            KeyCode::Tab => menu.layout = (menu.layout + 1) % LAYOUTS.len(),
+            KeyCode::Return => menu.waiting = true,
+            KeyCode::Escape => next_state.set(GameState::MainMenu),
+            _ => return,
+        }
+    }
+
+    for mut text in query_text.iter_mut() {
+        text.sections[0].value = bindings_menu_text(&bindings, &menu);
+    }
+}
+
+pub fn save_bindings(bindings: Res<KeyBindings>, config: Res<GameConfig>) {
+    match bindings.save(&config.keys) {
+        Ok(()) => println!("Key bindings saved to {}", config.keys.display()),
+        Err(message) => eprintln!("{message}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bindings_file() {
+        let mut bindings = KeyBindings::default();
+        bindings.layouts[0].set(Action::MoveLeft, KeyCode::J);
+        bindings.layouts[2].set(Action::HardDrop, KeyCode::NumpadEnter);
+
+        let text = bindings.to_string();
+        assert!(text.contains("[versus 2]\nmove_left Left\n"));
+        assert!(text.parse::<KeyBindings>() == Ok(bindings.clone()));
+
+        // missing actions keep their default keys
+        let partial: KeyBindings = "puyo_clone keys\n[single]\nmove_left J\n".parse().unwrap();
+        assert!(partial.keys(Player(0), 1).key(Action::MoveLeft) == KeyCode::J);
+        assert!(partial.keys(Player(1), 2) == KeyBindings::default().keys(Player(1), 2));
+    }
+
+    #[test]
+    fn test_invalid_bindings_file() {
+        assert!("".parse::<KeyBindings>().is_err());
+        assert!("puyo_clone keys\nmove_left J".parse::<KeyBindings>().is_err());
+        assert!("puyo_clone keys\n[single]\nmove_left Joystick".parse::<KeyBindings>().is_err());
+        assert!("puyo_clone keys\n[single]\njump J".parse::<KeyBindings>().is_err());
+        assert!("puyo_clone keys\n[trio]\nmove_left J".parse::<KeyBindings>().is_err());
+
+        // a key does one thing, only the versus players share the pause key
+        assert!("puyo_clone keys\n[single]\nmove_left Right".parse::<KeyBindings>().is_err());
+        assert!("puyo_clone keys\n[versus 1]\nmove_left Left".parse::<KeyBindings>().is_err());
+        let swapped = "puyo_clone keys\n[single]\nmove_left Right\nmove_right Left";
+        assert!(swapped.parse::<KeyBindings>().is_ok());
+        assert!(KeyBindings::default().check().is_ok());
+    }
+}
@@ -0,0 +1,237 @@
+use bevy::{app::AppExit, prelude::*};
+use puyo_clone::{
+    config::{GameConfig, MAX_COLORS, MIN_COLORS},
+    sim::game::Outcome,
+};
+
+use crate::{
+    game_objects::{
+        bindings::Action,
+        player::{Controls, CpuControls, InputDevices, Player},
+        simulation::GameSim,
+    },
+};
+
+const OVERLAY_SIZE: Vec2 = Vec2::new(480., 400.);
+const OVERLAY_FONT_SIZE: f32 = 40.;
+const OVERLAY_COLOR: Color = Color::rgba(0., 0., 0., 0.8);
+
+#[derive(States, Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum GameState {
+    #[default]
+    MainMenu,
+    Playing,
+    Paused,
+    GameOver,
+    KeyBindings,
+}
+
+/// Sent when a board tops out.
+#[derive(Event)]
+pub struct GameOverEvent {
+    pub outcome: Outcome,
+    /// Score of the first board, shown when playing alone.
+    pub score: u64,
+}
+
+/// Clears every board and starts over with new pairs.
+#[derive(Event, Default)]
+pub struct RestartEvent;
+
+/// Menus and messages drawn over the boards, despawned when leaving their state.
+#[derive(Component)]
+pub struct Overlay;
+
+pub fn spawn_overlay(commands: &mut Commands, text: String, size: Vec2, font_size: f32) {
+    commands.spawn((
+        Overlay,
+        SpriteBundle {
+            sprite: Sprite {
+                color: OVERLAY_COLOR,
+                custom_size: Some(size),
+                ..default()
+            },
+            transform: Transform::from_xyz(0., 0., 9.),
+            ..default()
+        },
+    ));
+    commands.spawn((
+        Overlay,
+        Text2dBundle {
+            text: Text::from_section(
+                text,
+                TextStyle {
+                    font_size,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            )
+            .with_alignment(TextAlignment::Center),
+            transform: Transform::from_xyz(0., 0., 10.),
+            ..default()
+        },
+    ));
+}
+
+fn main_menu_text(config: &GameConfig) -> String {
+    format!(
+        "Puyo Clone\n1: Single player\n2: Versus\n3: Versus CPU\n\
+         C: Colors ({})\nL: CPU level ({})\nK: Keys\nEsc: Quit",
+        config.n_colors, config.cpu_level
+    )
+}
+
+pub fn spawn_camera(mut commands: Commands) {
+    commands.spawn(Camera2dBundle::default());
+}
+
+pub fn despawn_overlay(mut commands: Commands, query_overlay: Query<Entity, With<Overlay>>) {
+    for entity in query_overlay.iter() {
+        commands.entity(entity).despawn();
+    }
+}
+
+pub fn show_main_menu(mut commands: Commands, config: Res<GameConfig>) {
+    spawn_overlay(&mut commands, main_menu_text(&config), OVERLAY_SIZE, OVERLAY_FONT_SIZE);
+}
+
+pub fn show_pause_menu(mut commands: Commands) {
+    spawn_overlay(
+        &mut commands,
+        "Paused\nEsc: Resume\nR: Restart\nQ: Quit".to_string(),
+        OVERLAY_SIZE,
+        OVERLAY_FONT_SIZE,
+    );
+}
+
+pub fn show_game_over(
+    mut commands: Commands,
+    mut game_over_event: EventReader<GameOverEvent>,
+    config: Res<GameConfig>,
+) {
+    let Some(event) = game_over_event.read().last() else { return };
+
+    let result = match event.outcome {
+        _ if config.players == 1 => format!("Score: {}", event.score),
+        Outcome::Draw => "Draw".to_string(),
+        Outcome::Loser(loser) => {
+            // whoever tops out first loses
+            let winner = (loser + 1) % config.players;
+            if config.cpu && winner == 1 {
+                "CPU wins".to_string()
+            } else {
+                format!("Player {} wins", winner + 1)
+            }
+        }
+    };
+
+    spawn_overlay(
+        &mut commands,
+        format!("Game Over\n{result}\nR: Restart\nQ: Quit"),
+        OVERLAY_SIZE,
+        OVERLAY_FONT_SIZE,
+    );
+}
+
+pub fn main_menu_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut config: ResMut<GameConfig>,
+    mut query_text: Query<&mut Text, With<Overlay>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut app_exit_event: EventWriter<AppExit>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Key1) {
+        config.players = 1;
+        config.cpu = false;
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::Key2) {
+        config.players = 2;
+        config.cpu = false;
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::Key3) {
+        config.players = 2;
+        config.cpu = true;
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::C) {
+        config.n_colors = if config.n_colors < MAX_COLORS {
+            config.n_colors + 1
+        } else {
+            MIN_COLORS
+        };
+        for mut text in query_text.iter_mut() {
+            text.sections[0].value = main_menu_text(&config);
+        }
+    } else if keyboard_input.just_pressed(KeyCode::L) {
+        config.cpu_level = config.cpu_level.next();
+        for mut text in query_text.iter_mut() {
+            text.sections[0].value = main_menu_text(&config);
+        }
+    } else if keyboard_input.just_pressed(KeyCode::K) {
+        next_state.set(GameState::KeyBindings);
+    } else if keyboard_input.just_pressed(KeyCode::Escape) {
+        app_exit_event.send(AppExit);
+    }
+}
+
+/// Whether a player pressed their pause key or button.
+fn pause_pressed(devices: &InputDevices, query_controls: &Query<(&Player, &Controls)>) -> bool {
+    query_controls
+        .iter()
+        .any(|(&player, controls)| devices.just_pressed(player, controls, Action::Pause))
+}
+
+pub fn pause_game(
+    devices: InputDevices,
+    query_controls: Query<(&Player, &Controls)>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if pause_pressed(&devices, &query_controls) {
+        next_state.set(GameState::Paused);
+    }
+}
+
+pub fn pause_menu_input(
+    devices: InputDevices,
+    query_controls: Query<(&Player, &Controls)>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut restart_event: EventWriter<RestartEvent>,
+) {
+    let keyboard_input = &devices.keyboard;
+    let resume = keyboard_input.just_pressed(KeyCode::Escape);
+    if resume || pause_pressed(&devices, &query_controls) {
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::R) {
+        restart_event.send_default();
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::Q) {
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+pub fn game_over_input(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut next_state: ResMut<NextState<GameState>>,
+    mut restart_event: EventWriter<RestartEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::R) {
+        restart_event.send_default();
+        next_state.set(GameState::Playing);
+    } else if keyboard_input.just_pressed(KeyCode::Q) {
+        next_state.set(GameState::MainMenu);
+    }
+}
+
+pub fn reset_board(mut sim: ResMut<GameSim>, mut query_cpu: Query<&mut CpuControls>) {
+    sim.reset();
+    for mut cpu in query_cpu.iter_mut() {
+        cpu.0.reset();
+    }
+}
+
+/// Removes the boards and everything on them when going back to the main menu.
+pub fn despawn_boards(mut commands: Commands, query_board: Query<Entity, With<Player>>) {
+    for entity in query_board.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+    commands.remove_resource::<GameSim>();
+}
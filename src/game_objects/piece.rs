@@ -1,91 +1,88 @@
-use std::{task::Wake, cmp::min};
-
 use bevy::{
     math::{vec2, vec3},
     prelude::*,
+    sprite::Anchor,
 };
-use rand::{rngs::StdRng, RngCore, SeedableRng};
-
-use crate::game_objects::{
-    fall::{Fall, FallState},
-    grid::{GameGrid, GridPosition},
-    movement::DASTimer,
+use puyo_clone::{
+    config::GameConfig,
+    cpu::Cpu,
+    sim::{
+        bag::PREVIEW_LENGTH,
+        board::{Board, GRID_HEIGHT, GRID_WIDTH, SUBCELLS},
+        game::Game,
+        grid::GridPosition,
+        piece::PieceColor,
+    },
 };
 
-const REPEAT_DELAY: f32 = 0.03;
-const START_DELAY: f32 = 0.1;
+use crate::{
+    game_objects::{
+        bindings::KeyBindings,
+        player::{Controls, CpuControls, Player},
+        score::{AllClearText, ScoreText},
+        simulation::GameSim,
+    },
+};
 
 const PIECE_SIZE: f32 = 32.;
-const PIECE_FALL_SPEED: f32 = 150.;
 
-const STARTING_ROW: isize = 18;
-const STARTING_COL: isize = 5;
-const GRID_HEIGHT: usize = 20;
-const GRID_WIDTH: usize = 10;
 const LEFT_BOTTOM_CORNER: Vec2 = vec2(-200., -300.);
+const VERSUS_BOARD_SPACING: f32 = 640.;
 
-const MIN_SIZE_SCORE: usize = 4;
+const PREVIEW_COL: isize = GRID_WIDTH as isize + 1;
+const PREVIEW_ROWS: [isize; PREVIEW_LENGTH] = [15, 11];
 
 const RED: Color = Color::rgb(1., 0., 0.);
 const BLUE: Color = Color::rgb(0., 0., 1.);
 const GREEN: Color = Color::rgb(0., 1., 0.);
 const PURPLE: Color = Color::rgb(0.5, 0., 0.5);
-
-#[derive(Component, Clone, Copy, Debug, PartialEq, Eq)]
-pub enum PieceColor {
-    Red,
-    Blue,
-    Purple,
-    Green,
+const YELLOW: Color = Color::rgb(1., 1., 0.);
+const CYAN: Color = Color::rgb(0., 1., 1.);
+const NUISANCE: Color = Color::rgb(0.8, 0.8, 0.8);
+
+/// Display colors of the pieces, indexed by `PieceColor`.
+#[derive(Resource)]
+pub struct Palette {
+    pub colors: [Color; PieceColor::ALL.len()],
+    pub nuisance: Color,
 }
 
-impl PieceColor {
-    fn get_color(self) -> Color {
-        match self {
-            PieceColor::Red => RED,
-            PieceColor::Blue => BLUE,
-            PieceColor::Green => GREEN,
-            PieceColor::Purple => PURPLE,
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: [RED, BLUE, PURPLE, GREEN, YELLOW, CYAN],
+            nuisance: NUISANCE,
         }
     }
 }
 
-#[derive(Component, Debug, Clone, Copy)]
-pub struct Piece {
-    pub color: PieceColor,
+impl Palette {
+    pub fn get_color(&self, color: PieceColor) -> Color {
+        match color {
+            PieceColor::Nuisance => self.nuisance,
+            color => self.colors[color as usize],
+        }
+    }
 }
 
+/// One of the sprites a board draws its pieces with. They are reused every frame, the
+/// ones left over are hidden.
 #[derive(Component)]
-pub struct Controllable;
-
-#[derive(Event, Default)]
-pub struct PairLandedEvent;
-
-#[derive(Event)]
-pub struct PieceLandedEvent {
-    pub entity: Entity,
-}
-
-impl PieceLandedEvent {
-    pub fn new(entity: Entity) -> Self {
-        Self { entity }
-    }
+pub struct PieceSprite {
+    index: usize,
 }
 
 #[derive(Bundle)]
 pub struct PieceBundle {
-    color: PieceColor,
     sprite_bundle: SpriteBundle,
-    piece: Piece,
 }
 
 impl PieceBundle {
-    pub fn new(color: PieceColor, position: Vec3) -> Self {
+    pub fn new(color: PieceColor, position: Vec3, palette: &Palette) -> Self {
         PieceBundle {
-            color,
             sprite_bundle: SpriteBundle {
                 sprite: Sprite {
-                    color: color.get_color(),
+                    color: palette.get_color(color),
                     ..default()
                 },
                 transform: Transform {
@@ -95,138 +92,103 @@ impl PieceBundle {
                 },
                 ..default()
             },
-            piece: Piece { color },
         }
     }
 }
 
 #[derive(Component)]
-pub struct Bag {
-    rng: StdRng,
-}
-
-impl Bag {
-    pub fn new() -> Self {
-        let rng = StdRng::from_entropy();
-        Self { rng }
-    }
-
-    fn piece_color(&mut self) -> PieceColor {
-        let r = self.rng.next_u32() % 4;
-
-        match r {
-            0 => PieceColor::Red,
-            1 => PieceColor::Blue,
-            2 => PieceColor::Purple,
-            3 => PieceColor::Green,
-            _ => panic!(),
-        }
-    }
-
-    pub fn new_piece(&mut self, position: Vec3) -> PieceBundle {
-        let color = self.piece_color();
-        PieceBundle::new(color, position)
-    }
-}
-
-#[derive(Clone, Copy)]
-pub enum PairOrientation {
-    ABVertical,
-    ABHorizontal,
-    BAVertical,
-    BAHorizontal,
+pub struct PreviewPiece {
+    index: usize,
+    order: usize,
 }
 
-#[derive(Component, Clone, Copy)]
-pub struct Pair {
-    orientation: PairOrientation,
+/// Where a board is drawn on the screen.
+#[derive(Component)]
+pub struct BoardView {
+    cell_size: f32,
+    left_bottom_corner: Vec2,
 }
 
-impl Pair {
-    pub fn new() -> Self {
-        Pair {
-            orientation: PairOrientation::ABVertical,
-        }
+impl BoardView {
+    pub fn position_to_vec3(&self, grid_position: GridPosition) -> Vec3 {
+        let x = self.left_bottom_corner.x + self.cell_size * (grid_position.col() as f32);
+        let y = self.left_bottom_corner.y + self.cell_size * (grid_position.row() as f32);
+        vec3(x, y, 0.)
     }
 
-    pub fn get_second_position(self, position: GridPosition) -> GridPosition {
-        match self.orientation {
-            PairOrientation::ABVertical => position.translate(-1, 0),
-            PairOrientation::BAVertical => position.translate(1, 0),
-            PairOrientation::ABHorizontal => position.translate(0, 1),
-            PairOrientation::BAHorizontal => position.translate(0, -1),
-        }
+    fn height_to_vec3(&self, col: isize, height: i32) -> Vec3 {
+        let x = self.left_bottom_corner.x + self.cell_size * (col as f32);
+        let y = self.left_bottom_corner.y + self.cell_size * (height as f32 / SUBCELLS as f32);
+        vec3(x, y, 0.)
     }
 
-    pub fn turn_clockwise(self) -> Self {
-        let new_orientation = match self.orientation {
-            PairOrientation::ABVertical => PairOrientation::BAHorizontal,
-            PairOrientation::BAHorizontal => PairOrientation::BAVertical,
-            PairOrientation::BAVertical => PairOrientation::ABHorizontal,
-            PairOrientation::ABHorizontal => PairOrientation::ABVertical,
-        };
-
-        Pair {
-            orientation: new_orientation,
+    /// Every piece in the visible field with where to draw it: the grid, the pair and the
+    /// pieces falling on their own.
+    fn pieces(&self, board: &Board) -> Vec<(PieceColor, Vec3)> {
+        let mut pieces = vec![];
+        let blink_hidden = board.popping_hidden();
+
+        for row in 0..board.grid.visible_height as isize {
+            for col in 0..board.grid.width as isize {
+                let position = GridPosition::new(row, col);
+                if blink_hidden && board.popping.contains(&position) {
+                    continue;
+                }
+                if let Some(color) = board.grid[position] {
+                    pieces.push((color, self.position_to_vec3(position)));
+                }
+            }
         }
-    }
 
-    pub fn adjust_transform(self, transform: &mut Transform, order: PieceOrder) {
-        match (self.orientation, order) {
-            (_, PieceOrder::First) => (),
-            (PairOrientation::ABVertical, _) => transform.translation = vec3(0., -PIECE_SIZE, 0.),
-            (PairOrientation::BAVertical, _) => transform.translation = vec3(0., PIECE_SIZE, 0.),
-            (PairOrientation::ABHorizontal, _) => transform.translation = vec3(PIECE_SIZE, 0., 0.),
-            (PairOrientation::BAHorizontal, _) => transform.translation = vec3(-PIECE_SIZE, 0., 0.),
+        let pair_pieces = board.pair.iter().flat_map(|pair| pair.pieces());
+        for piece in pair_pieces.chain(board.falling.iter().copied()) {
+            if board.grid.is_hidden(piece.position()) {
+                continue;
+            }
+            pieces.push((piece.color, self.height_to_vec3(piece.col, piece.height)));
         }
-    }
-}
 
-#[derive(Component, Clone, Copy)]
-pub enum PieceOrder {
-    First,
-    Second,
+        pieces
+    }
 }
 
-pub fn spawn_piece(mut commands: Commands, mut query: Query<(&mut Bag, &GameGrid)>) {
-    let (mut bag, grid) = query.single_mut();
-    let grid_position = GridPosition::new(STARTING_ROW, STARTING_COL);
-
-    println!("Spawning piece");
-    commands
-        .spawn((
-            VisibilityBundle {
-                visibility: Visibility::Visible,
-                ..default()
-            },
-            Pair::new(),
-            Fall::new(PIECE_FALL_SPEED),
-            Transform::from_translation(grid.position_to_vec3(grid_position)),
-            GlobalTransform::IDENTITY,
-            grid_position,
-        ))
-        .with_children(|parent| {
-            parent.spawn((PieceOrder::First, bag.new_piece(vec3(0., 0., 0.))));
-            parent.spawn((PieceOrder::Second, bag.new_piece(vec3(0., -PIECE_SIZE, 0.))));
-        });
+/// Boards are side by side in versus, centered as a whole.
+fn board_corner(player: Player, n_players: usize) -> Vec2 {
+    let offset = (player.0 as f32 - 0.5 * (n_players - 1) as f32) * VERSUS_BOARD_SPACING;
+    LEFT_BOTTOM_CORNER + vec2(offset, 0.)
 }
 
-pub fn setup(mut commands: Commands) {
-    commands.spawn(Camera2dBundle::default());
+pub fn setup(
+    mut commands: Commands,
+    config: Res<GameConfig>,
+    bindings: Res<KeyBindings>,
+    palette: Res<Palette>,
+) {
+    let game = Game::new(&config.settings());
 
-    let bag = Bag::new();
+    for player in (0..config.players).map(Player) {
+        let board = &game.boards[player.0];
+        spawn_board(&mut commands, &config, &bindings, &palette, board, player);
+    }
 
-    let grid = GameGrid::new(
-        GRID_HEIGHT,
-        GRID_WIDTH,
-        vec![None; GRID_WIDTH * GRID_HEIGHT],
-        PIECE_SIZE,
-        LEFT_BOTTOM_CORNER,
-    );
+    commands.insert_resource(GameSim(game));
+}
 
-    let input_timer = DASTimer::new(REPEAT_DELAY, START_DELAY);
+fn spawn_board(
+    commands: &mut Commands,
+    config: &GameConfig,
+    bindings: &KeyBindings,
+    palette: &Palette,
+    board: &Board,
+    player: Player,
+) {
+    let left_bottom_corner = board_corner(player, config.players);
+    let view = BoardView {
+        cell_size: PIECE_SIZE,
+        left_bottom_corner,
+    };
 
-    let grid_middle = LEFT_BOTTOM_CORNER
+    let grid_middle = left_bottom_corner
         + PIECE_SIZE * 0.5 * vec2((GRID_WIDTH - 1) as f32, (GRID_HEIGHT - 1) as f32);
     let grid_middle = vec3(grid_middle.x, grid_middle.y, -1.);
     let grid_size = vec3(
@@ -247,117 +209,118 @@ pub fn setup(mut commands: Commands) {
         ..default()
     };
 
-    commands.spawn((bag, grid, input_timer, grid_background));
+    let score_position = vec3(
+        grid_middle.x + 0.5 * grid_size.x + PIECE_SIZE,
+        grid_middle.y + 0.5 * grid_size.y,
+        0.,
+    );
+    commands.spawn((
+        ScoreText,
+        player,
+        Text2dBundle {
+            text: Text::from_section(
+                "Score: 0\nChain: 0",
+                TextStyle {
+                    font_size: 24.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            text_anchor: Anchor::TopLeft,
+            transform: Transform::from_translation(score_position),
+            ..default()
+        },
+    ));
+
+    commands.spawn((
+        AllClearText,
+        player,
+        Text2dBundle {
+            text: Text::from_section(
+                "ALL CLEAR",
+                TextStyle {
+                    font_size: 40.,
+                    color: Color::WHITE,
+                    ..default()
+                },
+            ),
+            transform: Transform::from_translation(grid_middle.truncate().extend(1.)),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+    ));
+
+    for (index, pair) in board.bag.preview().enumerate() {
+        let position = GridPosition::new(PREVIEW_ROWS[index], PREVIEW_COL);
+        for (order, &color) in pair.iter().enumerate() {
+            let translation = view.position_to_vec3(position.translate(-(order as isize), 0));
+            commands.spawn((
+                PreviewPiece { index, order },
+                player,
+                PieceBundle::new(color, translation, palette),
+            ));
+        }
+    }
+
+    let mut board_entity = commands.spawn((view, player, grid_background));
+    if config.cpu && player.0 == 1 {
+        board_entity.insert(CpuControls(Cpu::new(config.cpu_level)));
+    } else {
+        // against the computer the keyboard and the gamepads are all for the first player
+        let n_humans = if config.cpu { 1 } else { config.players };
+        board_entity.insert(Controls {
+            keys: bindings.keys(player, n_humans).clone(),
+            n_humans,
+        });
+    }
 }
 
-pub fn spawn_next_piece(
+pub fn draw_boards(
     mut commands: Commands,
-    mut land_event: EventReader<PairLandedEvent>,
-    query_entity: Query<(Entity, &GridPosition, &Pair, &Children)>,
-    query_children: Query<(Entity, &Piece, &PieceOrder)>,
-    mut query_bag: Query<(&mut Bag, &GameGrid)>,
+    sim: Res<GameSim>,
+    query_view: Query<(&BoardView, &Player)>,
+    mut query_sprite: Query<(&PieceSprite, &Player, &mut Transform, &mut Sprite, &mut Visibility)>,
+    palette: Res<Palette>,
 ) {
-    let mut flag = false;
-    for event in land_event.read() {
-        flag = true;
-    }
-
-    if flag {
-        let (mut bag, grid) = query_bag.single_mut();
-        let (entity_pair, position1, pair, children) = query_entity.single();
-        let position2 = pair.get_second_position(*position1);
-
-        // despawn children and spawn them with commands
-        for &child in children.iter() {
-            match query_children.get(child) {
-                Ok((entity, piece, PieceOrder::First)) => {
-                    commands.spawn((
-                        PieceBundle::new(piece.color, grid.position_to_vec3(*position1)),
-                        *position1,
-                        Fall::new(PIECE_FALL_SPEED),
-                    ));
-                    commands.get_entity(entity).unwrap().despawn();
+    for (view, &board) in query_view.iter() {
+        let pieces = view.pieces(&sim.boards[board.0]);
+        let mut n_sprites = 0;
+
+        for (piece_sprite, &player, mut transform, mut sprite, mut visibility) in query_sprite.iter_mut() {
+            if player != board { continue }
+            n_sprites += 1;
+
+            match pieces.get(piece_sprite.index) {
+                Some(&(color, translation)) => {
+                    transform.translation = translation;
+                    sprite.color = palette.get_color(color);
+                    *visibility = Visibility::Visible;
                 }
-                Ok((entity, piece, PieceOrder::Second)) => {
-                    commands.spawn((
-                        PieceBundle::new(piece.color, grid.position_to_vec3(position2)),
-                        position2,
-                        Fall::new(PIECE_FALL_SPEED),
-                    ));
-                    commands.get_entity(entity).unwrap().despawn();
-                }
-                _ => (),
+                None => *visibility = Visibility::Hidden,
             }
         }
 
-        commands.entity(entity_pair).remove::<Pair>();
-
-        // spawn new piece
-        let starting_position = GridPosition::new(STARTING_ROW, STARTING_COL);
-        commands
-            .spawn((
-                VisibilityBundle {
-                    visibility: Visibility::Visible,
-                    ..default()
-                },
-                Pair::new(),
-                Fall::new(PIECE_FALL_SPEED),
-                Transform::from_translation(grid.position_to_vec3(starting_position)),
-                GlobalTransform::IDENTITY,
-                starting_position,
-            ))
-            .with_children(|parent| {
-                parent.spawn((PieceOrder::First, bag.new_piece(vec3(0., 0., 0.))));
-                parent.spawn((PieceOrder::Second, bag.new_piece(vec3(0., -PIECE_SIZE, 0.))));
-            });
-    }
-}
-
-pub fn check_connected(
-    mut commands: Commands,
-    mut query_grid: Query<&mut GameGrid>,
-    query_position: Query<&GridPosition>,
-    mut query_fall: Query<&mut Fall>,
-    mut land_event: EventReader<PieceLandedEvent>,
-){
-    let mut grid = query_grid.single_mut();
-    let mut conn_comps: Vec<Vec<GridPosition>> = Vec::with_capacity(land_event.len());
-
-    for event in land_event.read() {
-        if let Ok(grid_position) = query_position.get(event.entity) {
-            conn_comps.push(grid.find_conn_comp(*grid_position));
+        for (index, &(color, translation)) in pieces.iter().enumerate().skip(n_sprites) {
+            commands.spawn((
+                PieceSprite { index },
+                board,
+                PieceBundle::new(color, translation, &palette),
+            ));
         }
     }
+}
 
-    let mut min_heights = vec![grid.height as isize; grid.width];
-
-    for conn_comp in conn_comps {
-        if conn_comp.len() < MIN_SIZE_SCORE { continue }
-
-        for position in conn_comp {
-            if let Some((_, entity)) = grid[position] {
-                commands.entity(entity).despawn();
-            }
-
-            grid[position] = None;
-
-            min_heights[position.col() as usize] = min(position.row(), min_heights[position.col() as usize]);
-        }
+pub fn update_preview(
+    sim: Res<GameSim>,
+    mut query_preview: Query<(&PreviewPiece, &Player, &mut Sprite)>,
+    palette: Res<Palette>,
+) {
+    if !sim.is_changed() {
+        return;
     }
 
-
-    for col in 0..grid.width {
-        for row in min_heights[col as usize]..(grid.height as isize) {
-            let position = GridPosition::new(row, col as isize);
-
-            if let Some((_, entity)) = grid[position] {
-                grid[position] = None;
-
-                if let Ok(mut fall) = query_fall.get_mut(entity) {
-                    fall.state = FallState::Normal;
-                    // println!("{row}, {col}")
-                }
-            }
-        }
+    for (preview, player, mut sprite) in query_preview.iter_mut() {
+        let pairs: Vec<&[PieceColor; 2]> = sim.boards[player.0].bag.preview().collect();
+        sprite.color = palette.get_color(pairs[preview.index][preview.order]);
     }
 }
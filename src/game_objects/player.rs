@@ -0,0 +1,123 @@
+use bevy::{ecs::system::SystemParam, prelude::*};
+use puyo_clone::{cpu::Cpu, sim::input::PlayerInput};
+
+use crate::game_objects::bindings::{Action, ActionKeys};
+
+/// Identifies a board. The board's view, its piece sprites and texts all carry the same
+/// `Player`, which is also the index of the board in the simulation.
+#[derive(Component, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Player(pub usize);
+
+/// Marks a board played by the computer instead of the keyboard.
+#[derive(Component)]
+pub struct CpuControls(pub Cpu);
+
+/// Deflection of the left stick past which it counts as a held direction.
+const STICK_THRESHOLD: f32 = 0.5;
+
+/// Keys of a player at the keyboard, who may also play with a gamepad.
+#[derive(Component)]
+pub struct Controls {
+    pub keys: ActionKeys,
+    /// Players sharing the keyboard and the gamepads.
+    pub n_humans: usize,
+}
+
+/// Gamepad button of every action, the same on every gamepad.
+fn gamepad_button(action: Action) -> GamepadButtonType {
+    match action {
+        Action::MoveLeft => GamepadButtonType::DPadLeft,
+        Action::MoveRight => GamepadButtonType::DPadRight,
+        Action::SoftDrop => GamepadButtonType::DPadDown,
+        Action::RotateClockwise => GamepadButtonType::East,
+        Action::RotateCounterClockwise => GamepadButtonType::South,
+        Action::HardDrop => GamepadButtonType::DPadUp,
+        Action::Pause => GamepadButtonType::Start,
+    }
+}
+
+/// Which of `n_gamepads` gamepads `player` plays with. Gamepads go to the last of the
+/// `n_humans` players, so that in versus a single one is for the second player and the
+/// first keeps the keyboard.
+fn gamepad_index(player: usize, n_humans: usize, n_gamepads: usize) -> Option<usize> {
+    (player + n_gamepads.min(n_humans)).checked_sub(n_humans)
+}
+
+/// Everything the players play with: the keyboard, and the gamepads in the order they
+/// were connected in.
+#[derive(SystemParam)]
+pub struct InputDevices<'w> {
+    pub keyboard: Res<'w, Input<KeyCode>>,
+    gamepads: Res<'w, Gamepads>,
+    gamepad_buttons: Res<'w, Input<GamepadButton>>,
+    gamepad_axes: Res<'w, Axis<GamepadAxis>>,
+}
+
+impl InputDevices<'_> {
+    fn gamepad(&self, player: Player, controls: &Controls) -> Option<Gamepad> {
+        let mut gamepads: Vec<Gamepad> = self.gamepads.iter().collect();
+        gamepads.sort_by_key(|gamepad| gamepad.id);
+        gamepad_index(player.0, controls.n_humans, gamepads.len()).map(|index| gamepads[index])
+    }
+
+    /// Whether `player` holds the key or the gamepad button of `action`. The left stick
+    /// counts as the d-pad.
+    pub fn pressed(&self, player: Player, controls: &Controls, action: Action) -> bool {
+        if controls.keys.pressed(&self.keyboard, action) {
+            return true;
+        }
+        let Some(gamepad) = self.gamepad(player, controls) else { return false };
+
+        let tilted = |axis_type, sign: f32| {
+            let axis = GamepadAxis::new(gamepad, axis_type);
+            self.gamepad_axes.get(axis).is_some_and(|value| value * sign > STICK_THRESHOLD)
+        };
+        let stick = match action {
+            Action::MoveLeft => tilted(GamepadAxisType::LeftStickX, -1.),
+            Action::MoveRight => tilted(GamepadAxisType::LeftStickX, 1.),
+            Action::SoftDrop => tilted(GamepadAxisType::LeftStickY, -1.),
+            _ => false,
+        };
+        stick || self.gamepad_buttons.pressed(GamepadButton::new(gamepad, gamepad_button(action)))
+    }
+
+    pub fn just_pressed(&self, player: Player, controls: &Controls, action: Action) -> bool {
+        let button = self
+            .gamepad(player, controls)
+            .map(|gamepad| GamepadButton::new(gamepad, gamepad_button(action)));
+        controls.keys.just_pressed(&self.keyboard, action)
+            || button.is_some_and(|button| self.gamepad_buttons.just_pressed(button))
+    }
+
+    /// Buttons held by `player`. A direction held on the keyboard, the d-pad or the stick
+    /// repeats through the board's `DASTimer` all the same.
+    pub fn input(&self, player: Player, controls: &Controls) -> PlayerInput {
+        let pressed = |action| self.pressed(player, controls, action);
+        PlayerInput {
+            left: pressed(Action::MoveLeft),
+            right: pressed(Action::MoveRight),
+            down: pressed(Action::SoftDrop),
+            rotate_clockwise: pressed(Action::RotateClockwise),
+            rotate_counter_clockwise: pressed(Action::RotateCounterClockwise),
+            hard_drop: pressed(Action::HardDrop),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_gamepad_index() {
+        // alone, the first gamepad
+        assert!(gamepad_index(0, 1, 0).is_none());
+        assert!(gamepad_index(0, 1, 2) == Some(0));
+
+        // in versus, a single gamepad is for the second player
+        assert!(gamepad_index(0, 2, 1).is_none());
+        assert!(gamepad_index(1, 2, 1) == Some(0));
+        assert!(gamepad_index(0, 2, 3) == Some(0));
+        assert!(gamepad_index(1, 2, 3) == Some(1));
+    }
+}
@@ -0,0 +1,79 @@
+use bevy::prelude::*;
+use puyo_clone::{
+    config::GameConfig,
+    sim::replay::{Playback, Replay},
+};
+
+use crate::{
+    game_objects::{
+        game_state::{GameState, RestartEvent},
+        simulation::GameSim,
+    },
+};
+
+/// Every input of the game being played, written to `GameConfig::record`.
+#[derive(Resource)]
+pub struct Recording(pub Replay);
+
+/// The replay being played back in place of the keyboard, until the game it recorded is over.
+#[derive(Resource)]
+pub struct ReplayPlayback(pub Playback);
+
+pub fn start_recording(mut commands: Commands, config: Res<GameConfig>) {
+    if config.record.is_some() {
+        commands.insert_resource(Recording(Replay::new(config.settings())));
+    }
+}
+
+pub fn save_replay(recording: Option<Res<Recording>>, config: Res<GameConfig>) {
+    let (Some(recording), Some(path)) = (recording, &config.record) else { return };
+
+    match recording.0.save(path) {
+        Ok(()) => println!("Replay saved to {}", path.display()),
+        Err(message) => eprintln!("{message}"),
+    }
+}
+
+/// Saves the replay when going back to the main menu, the next game is recorded anew.
+pub fn stop_recording(
+    mut commands: Commands,
+    recording: Option<Res<Recording>>,
+    config: Res<GameConfig>,
+) {
+    save_replay(recording, config);
+    commands.remove_resource::<Recording>();
+}
+
+pub fn record_restart(sim: Res<GameSim>, recording: Option<ResMut<Recording>>) {
+    if let Some(mut recording) = recording {
+        recording.0.record_restart(sim.frame);
+    }
+}
+
+/// Replays skip the main menu.
+pub fn start_playback(
+    playback: Option<Res<ReplayPlayback>>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if playback.is_some() {
+        next_state.set(GameState::Playing);
+    }
+}
+
+/// Games started from the main menu after the replay are played with the keyboard.
+pub fn stop_playback(mut commands: Commands) {
+    commands.remove_resource::<ReplayPlayback>();
+}
+
+/// A game over that was followed by a restart in the replay restarts on its own.
+pub fn playback_restart(
+    sim: Res<GameSim>,
+    mut playback: ResMut<ReplayPlayback>,
+    mut restart_event: EventWriter<RestartEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    if playback.0.take_restart(sim.frame) {
+        restart_event.send_default();
+        next_state.set(GameState::Playing);
+    }
+}
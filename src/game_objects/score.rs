@@ -0,0 +1,38 @@
+use bevy::prelude::*;
+
+use crate::game_objects::{player::Player, simulation::GameSim};
+
+#[derive(Component)]
+pub struct ScoreText;
+
+/// Banner over a board whose last chain left the grid empty.
+#[derive(Component)]
+pub struct AllClearText;
+
+pub fn update_score_text(sim: Res<GameSim>, mut query_text: Query<(&mut Text, &Player), With<ScoreText>>) {
+    if !sim.is_changed() {
+        return;
+    }
+
+    for (mut text, player) in query_text.iter_mut() {
+        let score = &sim.boards[player.0].score;
+        text.sections[0].value = format!("Score: {}\nChain: {}", score.value, score.chain);
+    }
+}
+
+pub fn update_all_clear_text(
+    sim: Res<GameSim>,
+    mut query_text: Query<(&mut Visibility, &Player), With<AllClearText>>,
+) {
+    if !sim.is_changed() {
+        return;
+    }
+
+    for (mut visibility, player) in query_text.iter_mut() {
+        *visibility = if sim.boards[player.0].all_clear {
+            Visibility::Visible
+        } else {
+            Visibility::Hidden
+        };
+    }
+}
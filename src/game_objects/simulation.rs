@@ -0,0 +1,71 @@
+use std::ops::{Deref, DerefMut};
+
+use bevy::prelude::*;
+use puyo_clone::sim::{game::Game, input::PlayerInput};
+
+use crate::game_objects::{
+    game_state::{GameOverEvent, GameState},
+    player::{Controls, CpuControls, InputDevices, Player},
+    replay::{Recording, ReplayPlayback},
+};
+
+/// The game being played. Every rule lives in the simulation, the systems only feed it
+/// the players' inputs and draw its boards.
+#[derive(Resource)]
+pub struct GameSim(pub Game);
+
+impl Deref for GameSim {
+    type Target = Game;
+
+    fn deref(&self) -> &Game {
+        &self.0
+    }
+}
+
+impl DerefMut for GameSim {
+    fn deref_mut(&mut self) -> &mut Game {
+        &mut self.0
+    }
+}
+
+/// Advances the simulation by one frame, `FixedUpdate` runs at the simulation's frame rate.
+pub fn step_game(
+    mut sim: ResMut<GameSim>,
+    devices: InputDevices,
+    mut query_controls: Query<(&Player, Option<&Controls>, Option<&mut CpuControls>)>,
+    playback: Option<ResMut<ReplayPlayback>>,
+    recording: Option<ResMut<Recording>>,
+    mut game_over_event: EventWriter<GameOverEvent>,
+    mut next_state: ResMut<NextState<GameState>>,
+) {
+    let inputs = match playback {
+        Some(mut playback) => playback.0.next_inputs(&mut sim),
+        _ => {
+            let mut inputs = vec![PlayerInput::default(); sim.boards.len()];
+            for (player, controls, cpu) in query_controls.iter_mut() {
+                if let Some(controls) = controls {
+                    inputs[player.0] = devices.input(*player, controls);
+                } else if let Some(mut cpu) = cpu {
+                    inputs[player.0] = cpu.0.input(&sim.boards[player.0]);
+                }
+            }
+            inputs
+        }
+    };
+
+    if let Some(mut recording) = recording {
+        recording.0.record_inputs(sim.frame, &inputs);
+    }
+    let events = sim.step(&inputs);
+    for (player, _, cpu) in query_controls.iter_mut() {
+        if let Some(mut cpu) = cpu {
+            cpu.0.update(&events[player.0]);
+        }
+    }
+
+    if let Some(outcome) = sim.outcome() {
+        let score = sim.boards[0].score.value;
+        game_over_event.send(GameOverEvent { outcome, score });
+        next_state.set(GameState::GameOver);
+    }
+}
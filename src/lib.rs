@@ -0,0 +1,5 @@
+pub mod bot;
+pub mod config;
+pub mod cpu;
+pub mod sim;
+pub mod tui;
@@ -1,32 +1,145 @@
 //! Renders a 2D scene containing a single, moving sprite.
 mod game_objects;
 
-use bevy::{math::vec3, prelude::*};
+use std::{
+    env,
+    io::{self, BufReader},
+    process,
+};
+
+use bevy::prelude::*;
 
-use crate::game_objects::{
-    fall::{update_fall_pair, update_fall_piece},
-    movement::{rotate_pair, move_pair},
-    piece::{spawn_next_piece, PairLandedEvent, setup, spawn_piece, PieceLandedEvent, check_connected},
+use puyo_clone::{
+    bot,
+    config::GameConfig,
+    sim::{
+        game::FRAME_RATE,
+        replay::{Playback, Replay},
+    },
+};
+
+use crate::{
+    game_objects::{
+        bindings::{bindings_menu_input, save_bindings, show_bindings_menu, KeyBindings},
+        game_state::{
+            despawn_boards, despawn_overlay, game_over_input, main_menu_input, pause_game,
+            pause_menu_input, reset_board, show_game_over, show_main_menu, show_pause_menu,
+            spawn_camera, GameOverEvent, GameState, RestartEvent,
+        },
+        piece::{draw_boards, setup, update_preview, Palette},
+        replay::{
+            playback_restart, record_restart, save_replay, start_playback, start_recording,
+            stop_playback, stop_recording, ReplayPlayback,
+        },
+        score::{update_all_clear_text, update_score_text},
+        simulation::{step_game, GameSim},
+    },
 };
 
 fn main() {
-    App::new()
-        .add_plugins(DefaultPlugins)
-        .add_event::<PairLandedEvent>()
-        .add_event::<PieceLandedEvent>()
-        .add_systems(Startup, setup)
-        .add_systems(PostStartup, spawn_piece)
+    let mut config = match GameConfig::from_args(env::args().skip(1)) {
+        Ok(config) => config,
+        Err(message) => {
+            eprintln!("{message}");
+            process::exit(1);
+        }
+    };
+
+    if config.bot {
+        run_bot(&config);
+        return;
+    }
+
+    let mut app = App::new();
+
+    if let Some(path) = &config.replay {
+        let replay = match Replay::load(path) {
+            Ok(replay) => replay,
+            Err(message) => {
+                eprintln!("{message}");
+                process::exit(1);
+            }
+        };
+        println!("Replaying {}", path.display());
+        config.apply_settings(&replay.settings);
+        app.insert_resource(ReplayPlayback(Playback::new(replay)));
+    }
+    println!("Seed: {}", config.seed);
+
+    let bindings = match KeyBindings::load(&config.keys) {
+        Ok(bindings) => bindings,
+        Err(message) => {
+            eprintln!("{message}");
+            process::exit(1);
+        }
+    };
+
+    app.add_plugins(DefaultPlugins)
+        .insert_resource(config)
+        .insert_resource(bindings)
+        .init_resource::<Palette>()
+        .add_state::<GameState>()
+        .insert_resource(Time::<Fixed>::from_hz(FRAME_RATE))
+        .add_event::<GameOverEvent>()
+        .add_event::<RestartEvent>()
+        .add_systems(Startup, (spawn_camera, start_playback))
+        .add_systems(FixedUpdate, step_game.run_if(in_state(GameState::Playing)))
+        .add_systems(
+            Update,
+            (draw_boards, update_score_text, update_all_clear_text, update_preview)
+                .run_if(resource_exists::<GameSim>()),
+        )
+        .add_systems(
+            Update,
+            (
+                main_menu_input.run_if(in_state(GameState::MainMenu)),
+                pause_game.run_if(in_state(GameState::Playing)),
+                pause_menu_input.run_if(in_state(GameState::Paused)),
+                game_over_input.run_if(in_state(GameState::GameOver)),
+                bindings_menu_input.run_if(in_state(GameState::KeyBindings)),
+                playback_restart.run_if(
+                    in_state(GameState::GameOver).and_then(resource_exists::<ReplayPlayback>()),
+                ),
+                // restart in the same frame, before the game steps again
+                (record_restart, reset_board).run_if(on_event::<RestartEvent>()),
+            )
+                .chain(),
+        )
         .add_systems(
-            FixedUpdate,
+            OnEnter(GameState::MainMenu),
             (
-                move_pair,
-                rotate_pair,
-                update_fall_pair,
-                update_fall_piece,
-                check_connected,
-                spawn_next_piece,
+                (stop_recording, stop_playback).run_if(resource_exists::<GameSim>()),
+                despawn_boards,
+                show_main_menu,
             )
                 .chain(),
         )
+        .add_systems(OnExit(GameState::MainMenu), despawn_overlay)
+        .add_systems(
+            OnTransition {
+                from: GameState::MainMenu,
+                to: GameState::Playing,
+            },
+            (setup, start_recording),
+        )
+        .add_systems(OnEnter(GameState::Paused), show_pause_menu)
+        .add_systems(OnExit(GameState::Paused), despawn_overlay)
+        .add_systems(OnEnter(GameState::GameOver), (show_game_over, save_replay))
+        .add_systems(OnExit(GameState::GameOver), despawn_overlay)
+        .add_systems(OnEnter(GameState::KeyBindings), show_bindings_menu)
+        .add_systems(OnExit(GameState::KeyBindings), (despawn_overlay, save_bindings))
         .run();
 }
+
+/// Plays without a window, stdout only carries the protocol so messages go to stderr.
+fn run_bot(config: &GameConfig) {
+    let result = bot::run(&config.settings(), BufReader::new(io::stdin()), io::stdout())
+        .and_then(|replay| match &config.record {
+            Some(path) => replay.save(path),
+            None => Ok(()),
+        });
+    if let Err(message) = result {
+        eprintln!("{message}");
+        process::exit(1);
+    }
+}
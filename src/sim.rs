@@ -0,0 +1,13 @@
+//! The game rules, independent of any rendering. A `Game` is advanced one frame at a time
+//! from the buttons held by each player, which makes it reproducible from a seed and inputs.
+pub mod bag;
+pub mod bitboard;
+pub mod board;
+pub mod game;
+pub mod grid;
+pub mod input;
+pub mod nuisance;
+pub mod piece;
+pub mod placement;
+pub mod replay;
+pub mod score;
@@ -0,0 +1,171 @@
+use std::collections::VecDeque;
+
+use rand::{rngs::StdRng, seq::SliceRandom, RngCore, SeedableRng};
+
+use crate::sim::piece::PieceColor;
+
+pub const PREVIEW_LENGTH: usize = 2;
+/// Fewest and most colors a bag can deal.
+pub const MIN_COLORS: usize = 3;
+pub const MAX_COLORS: usize = PieceColor::ALL.len();
+
+const CLASSIC_SEQUENCE_LENGTH: usize = 128;
+const CLASSIC_LIMITED_PAIRS: usize = 2;
+const CLASSIC_LIMITED_COLORS: usize = 3;
+
+#[derive(Clone)]
+enum Generator {
+    Random,
+    Sequence {
+        pairs: Vec<[PieceColor; 2]>,
+        index: usize,
+    },
+}
+
+#[derive(Clone)]
+pub struct Bag {
+    rng: StdRng,
+    colors: &'static [PieceColor],
+    generator: Generator,
+    queue: VecDeque<[PieceColor; 2]>,
+}
+
+impl Bag {
+    /// Endless random pairs of the first `n_colors` colors, determined by `seed`.
+    pub fn new(seed: u64, n_colors: usize) -> Self {
+        let colors = &PieceColor::ALL[..n_colors];
+        Self::with_generator(StdRng::seed_from_u64(seed), colors, Generator::Random)
+    }
+
+    /// A looping sequence of pre-generated pairs, determined by `seed`. Every color appears
+    /// the same number of times, and the first pairs only use a limited set of colors.
+    pub fn classic(seed: u64, n_colors: usize) -> Self {
+        let colors = &PieceColor::ALL[..n_colors];
+        let mut rng = StdRng::seed_from_u64(seed);
+        let pairs = classic_sequence(&mut rng, colors);
+        Self::with_generator(rng, colors, Generator::Sequence { pairs, index: 0 })
+    }
+
+    fn with_generator(rng: StdRng, colors: &'static [PieceColor], generator: Generator) -> Self {
+        let mut bag = Self {
+            rng,
+            colors,
+            generator,
+            queue: VecDeque::with_capacity(PREVIEW_LENGTH + 1),
+        };
+        for _ in 0..PREVIEW_LENGTH {
+            let pair = bag.pair_colors();
+            bag.queue.push_back(pair);
+        }
+        bag
+    }
+
+    fn piece_color(&mut self) -> PieceColor {
+        let r = self.rng.next_u32() as usize % self.colors.len();
+        self.colors[r]
+    }
+
+    fn pair_colors(&mut self) -> [PieceColor; 2] {
+        match &mut self.generator {
+            Generator::Random => [self.piece_color(), self.piece_color()],
+            Generator::Sequence { pairs, index } => {
+                let pair = pairs[*index];
+                *index = (*index + 1) % pairs.len();
+                pair
+            }
+        }
+    }
+
+    /// Takes the pair at the front of the queue and generates a new one at the back.
+    pub fn next_pair(&mut self) -> [PieceColor; 2] {
+        let pair = self.pair_colors();
+        self.queue.push_back(pair);
+        self.queue.pop_front().unwrap()
+    }
+
+    /// Upcoming pairs, starting with the next one to be spawned.
+    pub fn preview(&self) -> impl Iterator<Item = &[PieceColor; 2]> {
+        self.queue.iter()
+    }
+}
+
+fn classic_sequence(rng: &mut StdRng, colors: &[PieceColor]) -> Vec<[PieceColor; 2]> {
+    let n_pieces = 2 * CLASSIC_SEQUENCE_LENGTH;
+    let mut pieces: Vec<PieceColor> = colors
+        .iter()
+        .cycle()
+        .take(n_pieces)
+        .copied()
+        .collect();
+    pieces.shuffle(rng);
+
+    // swap the colors that are not allowed at the start with allowed ones further down
+    let limited = &colors[..CLASSIC_LIMITED_COLORS.min(colors.len())];
+    for i in 0..2 * CLASSIC_LIMITED_PAIRS {
+        if limited.contains(&pieces[i]) {
+            continue;
+        }
+        if let Some(j) = (2 * CLASSIC_LIMITED_PAIRS..n_pieces).find(|&j| limited.contains(&pieces[j])) {
+            pieces.swap(i, j);
+        }
+    }
+
+    pieces.chunks(2).map(|pair| [pair[0], pair[1]]).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn take_pairs(bag: &mut Bag, n: usize) -> Vec<[PieceColor; 2]> {
+        (0..n).map(|_| bag.next_pair()).collect()
+    }
+
+    #[test]
+    fn test_same_seed() {
+        assert!(take_pairs(&mut Bag::new(7, 4), 50) == take_pairs(&mut Bag::new(7, 4), 50));
+        assert!(take_pairs(&mut Bag::new(7, 4), 50) != take_pairs(&mut Bag::new(8, 4), 50));
+        assert!(take_pairs(&mut Bag::classic(7, 4), 50) == take_pairs(&mut Bag::classic(7, 4), 50));
+    }
+
+    #[test]
+    fn test_classic_sequence() {
+        let colors = &PieceColor::ALL[..5];
+        let mut bag = Bag::classic(3, colors.len());
+        let pairs = take_pairs(&mut bag, 2 * CLASSIC_SEQUENCE_LENGTH);
+
+        for pair in &pairs[..CLASSIC_LIMITED_PAIRS] {
+            assert!(pair.iter().all(|color| colors[..CLASSIC_LIMITED_COLORS].contains(color)));
+        }
+
+        let counts: Vec<usize> = colors
+            .iter()
+            .map(|&color| {
+                pairs[..CLASSIC_SEQUENCE_LENGTH]
+                    .iter()
+                    .flatten()
+                    .filter(|&&c| c == color)
+                    .count()
+            })
+            .collect();
+        assert!(counts.iter().max().unwrap() - counts.iter().min().unwrap() <= 1);
+
+        // the sequence loops
+        assert!(pairs[..CLASSIC_SEQUENCE_LENGTH] == pairs[CLASSIC_SEQUENCE_LENGTH..]);
+    }
+
+    #[test]
+    fn test_n_colors() {
+        let pairs = take_pairs(&mut Bag::new(5, 3), 100);
+
+        assert!(pairs.iter().flatten().all(|color| PieceColor::ALL[..3].contains(color)));
+    }
+
+    #[test]
+    fn test_preview() {
+        let mut bag = Bag::new(11, 4);
+        let preview: Vec<[PieceColor; 2]> = bag.preview().copied().collect();
+
+        assert!(preview == take_pairs(&mut bag, PREVIEW_LENGTH));
+    }
+}
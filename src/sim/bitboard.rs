@@ -0,0 +1,394 @@
+use crate::sim::{
+    board::{GRID_HEIGHT, GRID_WIDTH, MIN_GROUP_SIZE},
+    grid::{GameGrid, Grid},
+    piece::PieceColor,
+    score::link_score,
+};
+
+/// The group-forming colors, then nuisance.
+const KINDS: usize = PieceColor::ALL.len() + 1;
+const NUISANCE: usize = KINDS - 1;
+
+/// One bit per row for every column, bit `row` of `columns[col]`.
+type Columns = [u32; GRID_WIDTH];
+/// The rows of the visible field, pieces in the hidden rows above never pop.
+const VISIBLE: u32 = (1 << GRID_HEIGHT) - 1;
+
+fn kind(color: PieceColor) -> usize {
+    match color {
+        PieceColor::Nuisance => NUISANCE,
+        color => PieceColor::ALL.iter().position(|&c| c == color).unwrap_or(NUISANCE),
+    }
+}
+
+fn color(kind: usize) -> PieceColor {
+    PieceColor::ALL.get(kind).copied().unwrap_or(PieceColor::Nuisance)
+}
+
+fn count(columns: &Columns) -> u32 {
+    columns.iter().map(|column| column.count_ones()).sum()
+}
+
+fn is_empty(columns: &Columns) -> bool {
+    columns.iter().all(|&column| column == 0)
+}
+
+fn visible(columns: &Columns) -> Columns {
+    columns.map(|column| column & VISIBLE)
+}
+
+/// The cells of `columns` and every cell next to them.
+fn dilate(columns: &Columns) -> Columns {
+    let mut dilated = *columns;
+    for col in 0..GRID_WIDTH {
+        dilated[col] |= columns[col] << 1 | columns[col] >> 1;
+        if col > 0 {
+            dilated[col] |= columns[col - 1];
+        }
+        if col + 1 < GRID_WIDTH {
+            dilated[col] |= columns[col + 1];
+        }
+    }
+    dilated
+}
+
+/// The cells of `mask` connected to `seed`, which lies in column `col`. Only the columns
+/// next to the group grown so far are looked at, most groups span one or two.
+fn flood(mask: &Columns, seed: Columns, col: usize) -> Columns {
+    let mut group = seed;
+    let (mut first, mut last) = (col, col);
+    loop {
+        let (from, to) = (first.saturating_sub(1), (last + 1).min(GRID_WIDTH - 1));
+        let mut grown = group;
+        for col in from..=to {
+            let mut column = group[col] | group[col] << 1 | group[col] >> 1;
+            if col > 0 {
+                column |= group[col - 1];
+            }
+            if col + 1 < GRID_WIDTH {
+                column |= group[col + 1];
+            }
+            grown[col] = column & mask[col];
+        }
+        if grown == group {
+            return group;
+        }
+        if grown[from] != 0 {
+            first = from;
+        }
+        if grown[to] != 0 {
+            last = to;
+        }
+        group = grown;
+    }
+}
+
+/// The cells of `mask` with at least `neighbours` (1 or 2) neighbours in `mask`.
+fn linked(mask: &Columns, neighbours: u32) -> Columns {
+    let mut linked = [0; GRID_WIDTH];
+    for col in 0..GRID_WIDTH {
+        let (above, below) = (mask[col] >> 1, mask[col] << 1);
+        let left = if col > 0 { mask[col - 1] } else { 0 };
+        let right = if col + 1 < GRID_WIDTH { mask[col + 1] } else { 0 };
+        let cells = if neighbours >= 2 {
+            above & (below | left | right) | below & (left | right) | left & right
+        } else {
+            above | below | left | right
+        };
+        linked[col] = mask[col] & cells;
+    }
+    linked
+}
+
+/// The bits of `value` selected by `mask`, packed at the bottom.
+fn compact(value: u32, mut mask: u32) -> u32 {
+    let mut packed = 0;
+    let mut bit = 0;
+    while mask != 0 {
+        let lowest = mask & mask.wrapping_neg();
+        if value & lowest != 0 {
+            packed |= 1 << bit;
+        }
+        bit += 1;
+        mask &= mask - 1;
+    }
+    packed
+}
+
+/// A settled grid packed into one bitboard per color, for searches that look at far more
+/// grids than `GameGrid` and its hash sets can handle. The grid has `GRID_WIDTH` columns
+/// and at most 32 rows.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct BitBoard {
+    height: usize,
+    kinds: [Columns; KINDS],
+}
+
+impl BitBoard {
+    pub fn new(height: usize) -> Self {
+        assert!(height <= u32::BITS as usize);
+        Self {
+            height,
+            kinds: [[0; GRID_WIDTH]; KINDS],
+        }
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> Option<PieceColor> {
+        (0..KINDS)
+            .find(|&kind| self.kinds[kind][col] >> row & 1 == 1)
+            .map(color)
+    }
+
+    pub fn set(&mut self, row: usize, col: usize, value: Option<PieceColor>) {
+        for columns in self.kinds.iter_mut() {
+            columns[col] &= !(1 << row);
+        }
+        if let Some(value) = value {
+            self.kinds[kind(value)][col] |= 1 << row;
+        }
+    }
+
+    fn occupied(&self) -> Columns {
+        let mut occupied = [0; GRID_WIDTH];
+        for columns in &self.kinds {
+            for (occupied, column) in occupied.iter_mut().zip(columns) {
+                *occupied |= column;
+            }
+        }
+        occupied
+    }
+
+    /// Lowest empty row of a column, the height when the column is full.
+    pub fn column_height(&self, col: usize) -> usize {
+        self.column_heights()[col]
+    }
+
+    pub fn column_heights(&self) -> [usize; GRID_WIDTH] {
+        self.occupied().map(|column| (u32::BITS - column.leading_zeros()) as usize)
+    }
+
+    /// Puts a piece on top of a column, returns false when the column is full.
+    pub fn drop_piece(&mut self, col: usize, color: PieceColor) -> bool {
+        let row = self.column_height(col);
+        if row >= self.height {
+            return false;
+        }
+        self.kinds[kind(color)][col] |= 1 << row;
+        true
+    }
+
+    /// Every group of pieces of the same color with its size, nuisance excluded.
+    pub fn groups(&self) -> Vec<(PieceColor, u32)> {
+        let mut groups: Vec<(PieceColor, u32)> =
+            self.masks(2).map(|(kind, group)| (color(kind), count(&group))).collect();
+        for kind in 0..NUISANCE {
+            let mut single = visible(&self.kinds[kind]);
+            for (single, linked) in single.iter_mut().zip(linked(&visible(&self.kinds[kind]), 1)) {
+                *single &= !linked;
+            }
+            groups.extend((0..count(&single)).map(|_| (color(kind), 1)));
+        }
+        groups
+    }
+
+    /// Masks of the groups of at least `min_size` pieces, and of some smaller ones. A group
+    /// of two has a piece with a neighbour of its color, a bigger one a piece with two.
+    fn masks(&self, min_size: u32) -> impl Iterator<Item = (usize, Columns)> + '_ {
+        let kinds = (0..NUISANCE).filter(move |&kind| count(&self.kinds[kind]) >= min_size);
+        kinds.flat_map(move |kind| {
+            let mask = visible(&self.kinds[kind]);
+            let mut seeds = if min_size <= 1 { mask } else { linked(&mask, min_size.min(3) - 1) };
+            std::iter::from_fn(move || {
+                let col = seeds.iter().position(|&column| column != 0)?;
+                let mut seed = [0; GRID_WIDTH];
+                seed[col] = seeds[col] & seeds[col].wrapping_neg();
+
+                let group = flood(&mask, seed, col);
+                for (seeds, group) in seeds.iter_mut().zip(&group) {
+                    *seeds &= !group;
+                }
+                Some((kind, group))
+            })
+        })
+    }
+
+    /// Size of the group that the piece at `row`, `col` belongs to, 0 for an empty cell and
+    /// 1 for nuisance or a hidden piece.
+    pub fn group_size(&self, row: usize, col: usize) -> u32 {
+        let Some(value) = self.get(row, col) else { return 0 };
+        if value == PieceColor::Nuisance || row >= GRID_HEIGHT {
+            return 1;
+        }
+        let mut seed = [0; GRID_WIDTH];
+        seed[col] = 1 << row;
+        count(&flood(&visible(&self.kinds[kind(value)]), seed, col))
+    }
+
+    /// Lets every piece fall to the bottom of its column.
+    pub fn fall(&mut self) {
+        let occupied = self.occupied();
+        for (col, &column) in occupied.iter().enumerate() {
+            // already packed at the bottom
+            if column & (column + 1) == 0 {
+                continue;
+            }
+            for columns in self.kinds.iter_mut() {
+                columns[col] = compact(columns[col], column);
+            }
+        }
+    }
+
+    /// Pops every group big enough along with the nuisance next to it, returns the color
+    /// and size of the groups that popped.
+    pub fn pop(&mut self) -> Vec<(PieceColor, usize)> {
+        let mut popped = [0; GRID_WIDTH];
+        let mut groups = vec![];
+        for (kind, group) in self.masks(MIN_GROUP_SIZE as u32) {
+            let size = count(&group);
+            if size >= MIN_GROUP_SIZE as u32 {
+                groups.push((color(kind), size as usize));
+                for (popped, group) in popped.iter_mut().zip(&group) {
+                    *popped |= group;
+                }
+            }
+        }
+        if groups.is_empty() {
+            return groups;
+        }
+
+        let around = dilate(&popped);
+        for (col, popped) in popped.iter_mut().enumerate() {
+            *popped |= around[col] & self.kinds[NUISANCE][col] & VISIBLE;
+        }
+        for columns in self.kinds.iter_mut() {
+            for (column, popped) in columns.iter_mut().zip(&popped) {
+                *column &= !popped;
+            }
+        }
+        groups
+    }
+
+    /// Pops and lets pieces fall until nothing pops anymore, returns the score of every
+    /// link of the chain.
+    pub fn resolve(&mut self) -> Vec<u64> {
+        let mut links = vec![];
+        loop {
+            let groups = self.pop();
+            if groups.is_empty() {
+                return links;
+            }
+            self.fall();
+            links.push(link_score(links.len() as u32 + 1, &groups));
+        }
+    }
+
+    pub fn is_clear(&self) -> bool {
+        self.kinds.iter().all(is_empty)
+    }
+}
+
+impl From<&GameGrid> for BitBoard {
+    fn from(grid: &GameGrid) -> Self {
+        assert!(grid.width == GRID_WIDTH);
+        let mut bitboard = BitBoard::new(grid.height);
+        for row in 0..grid.height {
+            for col in 0..grid.width {
+                bitboard.set(row, col, grid[[row as isize, col as isize]]);
+            }
+        }
+        bitboard
+    }
+}
+
+impl From<&BitBoard> for GameGrid {
+    fn from(bitboard: &BitBoard) -> Self {
+        let mut grid = Grid::new(bitboard.height, GRID_WIDTH, vec![None; bitboard.height * GRID_WIDTH])
+            .with_visible_height(bitboard.height.min(GRID_HEIGHT));
+        for row in 0..bitboard.height {
+            for col in 0..GRID_WIDTH {
+                grid[[row as isize, col as isize]] = bitboard.get(row, col);
+            }
+        }
+        grid
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{
+        board::{Board, HIDDEN_ROWS},
+        grid::GridPosition,
+    };
+
+    fn grid() -> GameGrid {
+        let mut grid = Board::empty_grid();
+        grid[[0, 0]] = Some(PieceColor::Blue);
+        grid[[1, 0]] = Some(PieceColor::Nuisance);
+        for col in 1..4 {
+            grid[[0, col]] = Some(PieceColor::Red);
+            grid[[1, col]] = Some(PieceColor::Blue);
+        }
+        grid[[0, 4]] = Some(PieceColor::Red);
+        grid[[1, 4]] = Some(PieceColor::Green);
+        grid[[0, 9]] = Some(PieceColor::Cyan);
+        grid
+    }
+
+    #[test]
+    fn test_conversion() {
+        let grid = grid();
+        let bitboard = BitBoard::from(&grid);
+        assert!(GameGrid::from(&bitboard) == grid);
+        assert!(bitboard.get(1, 0) == Some(PieceColor::Nuisance));
+        assert!(bitboard.column_height(4) == 2);
+        assert!(bitboard.column_height(5) == 0);
+
+        let mut grid = grid;
+        grid[[GRID_HEIGHT as isize, 9]] = Some(PieceColor::Red);
+        assert!(GameGrid::from(&BitBoard::from(&grid)) == grid);
+    }
+
+    #[test]
+    fn test_groups() {
+        let grid = grid();
+        let bitboard = BitBoard::from(&grid);
+
+        let mut sizes: Vec<u32> = bitboard.groups().iter().map(|&(_, size)| size).collect();
+        let mut expected: Vec<u32> = grid.groups().iter().map(|group| group.len() as u32).collect();
+        sizes.sort();
+        expected.sort();
+        assert!(sizes == expected);
+
+        let position = GridPosition::new(0, 2);
+        assert!(bitboard.group_size(0, 2) as usize == grid.find_conn_comp(position).len());
+        assert!(bitboard.group_size(1, 0) == 1);
+    }
+
+    #[test]
+    fn test_resolve() {
+        let mut bitboard = BitBoard::from(&grid());
+
+        // the reds pop, the blues fall next to the last one and pop with the nuisance
+        assert!(bitboard.resolve() == vec![40, 40 * 8]);
+        assert!(bitboard.get(0, 4) == Some(PieceColor::Green));
+        assert!(bitboard.get(0, 9) == Some(PieceColor::Cyan));
+        assert!(bitboard.groups().len() == 2);
+
+        bitboard.set(0, 4, None);
+        bitboard.set(0, 9, None);
+        assert!(bitboard.is_clear());
+
+        // a group reaching into the hidden row is one piece short
+        let mut bitboard = BitBoard::new(GRID_HEIGHT + HIDDEN_ROWS);
+        for row in GRID_HEIGHT - 3..=GRID_HEIGHT {
+            bitboard.set(row, 0, Some(PieceColor::Green));
+        }
+        assert!(bitboard.resolve().is_empty());
+        assert!(bitboard.group_size(GRID_HEIGHT - 1, 0) == 3);
+    }
+}
@@ -0,0 +1,819 @@
+use std::cmp::min;
+
+use crate::sim::{
+    bag::Bag,
+    grid::{GameGrid, Grid, GridPosition},
+    input::{DASTimer, PlayerInput, QuickTurnTimer, Shift},
+    nuisance::NuisanceQueue,
+    piece::{Pair, PieceColor},
+    score::Score,
+};
+
+/// Rows of the visible field.
+pub const GRID_HEIGHT: usize = 20;
+/// Rows above the field where pieces can rest, hidden and out of reach of pops. Pieces
+/// landing any higher are lost.
+pub const HIDDEN_ROWS: usize = 1;
+pub const GRID_WIDTH: usize = 10;
+pub const STARTING_ROW: isize = 18;
+pub const STARTING_COL: isize = 5;
+
+/// Pieces of one color it takes to pop, the bitboard and the CPU count with it too.
+pub const MIN_GROUP_SIZE: usize = 4;
+
+/// Heights are measured in fractions of a cell, so that pieces fall smoothly.
+pub const SUBCELLS: i32 = 64;
+/// Sub-cells per frame.
+const FALL_SPEED: i32 = 5;
+const FAST_MULT: i32 = 3;
+/// Sub-cells per frame of the halves of a pair that just landed.
+const TEAR_FALL_SPEED: i32 = 16;
+/// Frames popping groups stay on the board before they disappear.
+pub const POP_FRAMES: u32 = 32;
+/// Popping pieces blink, hidden and shown for this many frames in turn.
+pub const POP_BLINK_FRAMES: u32 = 4;
+/// Frames a pair resting on the stack can still slide and turn before it locks.
+pub const LOCK_DELAY: u32 = 30;
+/// Times moving or turning a grounded pair starts its lock delay over.
+const LOCK_RESETS: u32 = 8;
+
+/// Cells of a pair at the spawn, a board whose stack reaches either of them tops out.
+pub fn spawn_cells() -> [GridPosition; 2] {
+    let first = GridPosition::new(STARTING_ROW, STARTING_COL);
+    [first, Pair::new().get_second_position(first)]
+}
+
+fn round_row(height: i32) -> isize {
+    (height + SUBCELLS / 2).div_euclid(SUBCELLS) as isize
+}
+
+fn row_height(row: isize) -> i32 {
+    row as i32 * SUBCELLS
+}
+
+/// A single piece falling on its own, after a pop or a split pair.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct FallingPiece {
+    pub color: PieceColor,
+    pub col: isize,
+    pub height: i32,
+}
+
+impl FallingPiece {
+    pub fn new(color: PieceColor, position: GridPosition) -> Self {
+        Self {
+            color,
+            col: position.col(),
+            height: row_height(position.row()),
+        }
+    }
+
+    /// The cell the piece is closest to.
+    pub fn position(&self) -> GridPosition {
+        GridPosition::new(round_row(self.height), self.col)
+    }
+}
+
+/// The pair controlled by the player. `position` is the cell of the first piece.
+#[derive(Clone, Debug)]
+pub struct FallingPair {
+    pub pair: Pair,
+    pub colors: [PieceColor; 2],
+    pub position: GridPosition,
+    pub height: i32,
+    pub fast: bool,
+    /// Frames the pair has been resting on the stack, 0 while it falls.
+    pub lock_frames: u32,
+    pub lock_resets: u32,
+}
+
+impl FallingPair {
+    /// A pair at the spawn.
+    pub fn new(pair: Pair, colors: [PieceColor; 2]) -> Self {
+        let position = GridPosition::new(STARTING_ROW, STARTING_COL);
+        Self {
+            pair,
+            colors,
+            position,
+            height: row_height(position.row()),
+            fast: false,
+            lock_frames: 0,
+            lock_resets: 0,
+        }
+    }
+
+    /// Both pieces of the pair, at their current height.
+    pub fn pieces(&self) -> [FallingPiece; 2] {
+        let second = self.pair.get_second_position(self.position);
+        let offset = row_height(second.row() - self.position.row());
+        [
+            FallingPiece {
+                color: self.colors[0],
+                col: self.position.col(),
+                height: self.height,
+            },
+            FallingPiece {
+                color: self.colors[1],
+                col: second.col(),
+                height: self.height + offset,
+            },
+        ]
+    }
+
+    fn move_to(&mut self, position: GridPosition) {
+        self.height += row_height(position.row() - self.position.row());
+        self.position = position;
+    }
+
+    /// Moving or turning the pair on the ground starts its lock delay over, only so many
+    /// times so that it can't be kept from locking forever.
+    fn reset_lock(&mut self) {
+        if self.lock_frames > 0 && self.lock_resets < LOCK_RESETS {
+            self.lock_frames = 0;
+            self.lock_resets += 1;
+        }
+    }
+}
+
+/// What happened on a board during one frame.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BoardEvents {
+    pub pair_landed: bool,
+    /// Points scored by each chain link that popped.
+    pub links: Vec<u64>,
+    /// The chain that just ended left the grid empty.
+    pub all_clear: bool,
+    pub topped_out: bool,
+}
+
+/// What a board is doing. Once the pair lands, the board goes through tearing, falling and
+/// popping until nothing pops anymore, then the next pair spawns.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// The player moves the pair.
+    Control,
+    /// The halves of the pair that just landed drop to the stack on their own, fast, the
+    /// one hanging over a gap tearing away from the other.
+    Tear,
+    /// Loose pieces fall until all of them have landed, then the whole grid is checked
+    /// for groups.
+    Fall,
+    /// Groups that were found stay on the grid for the remaining frames, then pop.
+    Pop { frames: u32 },
+}
+
+/// The whole state of one player's board, advanced one frame at a time by `step`.
+#[derive(Clone)]
+pub struct Board {
+    pub grid: GameGrid,
+    pub phase: Phase,
+    pub pair: Option<FallingPair>,
+    pub falling: Vec<FallingPiece>,
+    /// Cells about to pop, during `Phase::Pop`.
+    pub popping: Vec<GridPosition>,
+    pub bag: Bag,
+    pub score: Score,
+    pub nuisance: NuisanceQueue,
+    pub topped_out: bool,
+    /// The last chain left the grid empty, until the next pair lands.
+    pub all_clear: bool,
+    /// Frames a grounded pair waits before it locks, unless it's pushed down.
+    pub lock_delay: u32,
+    /// Nuisance only falls once between two pairs.
+    nuisance_dropped: bool,
+    das_timer: DASTimer,
+    quick_turn_timer: QuickTurnTimer,
+    previous_input: PlayerInput,
+}
+
+impl Board {
+    /// The grid of a board before any piece lands: the field and the hidden rows above it.
+    pub fn empty_grid() -> GameGrid {
+        let height = GRID_HEIGHT + HIDDEN_ROWS;
+        Grid::new(height, GRID_WIDTH, vec![None; GRID_WIDTH * height])
+            .with_visible_height(GRID_HEIGHT)
+    }
+
+    pub fn new(bag: Bag, nuisance: NuisanceQueue) -> Self {
+        let mut board = Self {
+            grid: Self::empty_grid(),
+            phase: Phase::Control,
+            pair: None,
+            falling: vec![],
+            popping: vec![],
+            bag,
+            score: Score::default(),
+            nuisance,
+            topped_out: false,
+            all_clear: false,
+            lock_delay: LOCK_DELAY,
+            nuisance_dropped: false,
+            das_timer: DASTimer::default(),
+            quick_turn_timer: QuickTurnTimer::default(),
+            previous_input: PlayerInput::default(),
+        };
+        board.spawn_pair();
+        board
+    }
+
+    /// Empties the board and spawns a new pair. The bag goes on where it was.
+    pub fn reset(&mut self) {
+        self.grid.clear();
+        self.falling.clear();
+        self.popping.clear();
+        self.score = Score::default();
+        self.nuisance.pending = 0;
+        self.nuisance.leftover_points = 0;
+        self.topped_out = false;
+        self.all_clear = false;
+        self.nuisance_dropped = false;
+        self.das_timer.reset();
+        self.quick_turn_timer.reset();
+        self.previous_input = PlayerInput::default();
+        self.spawn_pair();
+    }
+
+    /// Whether the popping cells blink out this frame.
+    pub fn popping_hidden(&self) -> bool {
+        match self.phase {
+            Phase::Pop { frames } => (frames / POP_BLINK_FRAMES) % 2 == 1,
+            _ => false,
+        }
+    }
+
+    /// Whether a second refused rotation would flip the pair right now.
+    pub fn quick_turn_armed(&self) -> bool {
+        self.quick_turn_timer.is_armed()
+    }
+
+    fn spawn_pair(&mut self) {
+        self.pair = Some(FallingPair::new(Pair::new(), self.bag.next_pair()));
+        self.phase = Phase::Control;
+    }
+
+    pub fn step(&mut self, input: PlayerInput) -> BoardEvents {
+        let mut events = BoardEvents::default();
+        if self.topped_out {
+            return events;
+        }
+
+        match self.phase {
+            Phase::Control => {
+                self.move_pair(input);
+                self.rotate_pair(input);
+                let landed = if input.hard_drop && !self.previous_input.hard_drop {
+                    self.hard_drop_pair()
+                } else {
+                    self.fall_pair()
+                };
+                if landed {
+                    events.pair_landed = true;
+                    self.all_clear = false;
+                    if let Some(pair) = self.pair.take() {
+                        self.falling.extend(pair.pieces());
+                    }
+                    self.phase = Phase::Tear;
+                }
+            }
+            Phase::Tear | Phase::Fall => {
+                let speed = if self.phase == Phase::Tear { TEAR_FALL_SPEED } else { FALL_SPEED };
+                self.fall_pieces(speed);
+                if self.falling.is_empty() {
+                    self.check_groups(&mut events);
+                }
+            }
+            Phase::Pop { frames } if frames > 1 => self.phase = Phase::Pop { frames: frames - 1 },
+            Phase::Pop { .. } => {
+                self.pop();
+                self.phase = Phase::Fall;
+            }
+        }
+        self.previous_input = input;
+
+        events
+    }
+
+    fn move_pair(&mut self, input: PlayerInput) {
+        let Some(falling_pair) = self.pair.as_mut() else { return };
+
+        let (pair, position) = (falling_pair.pair, &mut falling_pair.position);
+        let moved = match self.das_timer.update(input.shift()) {
+            Some(Shift::Right) => self.grid.move_right_pair(pair, position),
+            Some(Shift::Left) => self.grid.move_left_pair(pair, position),
+            None => false,
+        };
+        if moved {
+            falling_pair.reset_lock();
+        }
+
+        falling_pair.fast = input.down;
+    }
+
+    fn rotate_pair(&mut self, input: PlayerInput) {
+        let Some(falling_pair) = self.pair.as_mut() else { return };
+        let (pair, position) = (falling_pair.pair, falling_pair.position);
+
+        self.quick_turn_timer.tick();
+
+        let turn = if input.rotate_clockwise && !self.previous_input.rotate_clockwise {
+            self.grid
+                .turn_clockwise_position(pair, position)
+                .map(|new_position| (pair.turn_clockwise(), new_position))
+        } else if input.rotate_counter_clockwise && !self.previous_input.rotate_counter_clockwise {
+            self.grid
+                .turn_counter_clockwise_position(pair, position)
+                .map(|new_position| (pair.turn_counter_clockwise(), new_position))
+        } else {
+            return;
+        };
+        let turn = match turn {
+            None if self.quick_turn_timer.is_armed() => self
+                .grid
+                .quick_turn_position(pair, position)
+                .map(|new_position| (pair.quick_turn(), new_position)),
+            turn => turn,
+        };
+
+        match turn {
+            Some((new_pair, new_position)) => {
+                falling_pair.pair = new_pair;
+                falling_pair.move_to(new_position);
+                falling_pair.reset_lock();
+                self.quick_turn_timer.reset();
+            }
+            // the first refused rotation arms the quick turn
+            None => self.quick_turn_timer.arm(),
+        }
+    }
+
+    /// Returns whether the pair landed this frame. A pair on the ground can still slide and
+    /// turn until its lock delay runs out, or right away when it is pushed down.
+    fn fall_pair(&mut self) -> bool {
+        let Some(falling_pair) = self.pair.as_mut() else { return false };
+
+        let speed = if falling_pair.fast { FALL_SPEED * FAST_MULT } else { FALL_SPEED };
+        falling_pair.height -= speed;
+        falling_pair.position = GridPosition::new(round_row(falling_pair.height), falling_pair.position.col());
+
+        let discretized_height = row_height(falling_pair.position.row());
+        if self.grid.can_move_down_pair(falling_pair.pair, falling_pair.position)
+            || falling_pair.height >= discretized_height
+        {
+            falling_pair.lock_frames = 0;
+            return false;
+        }
+        falling_pair.height = discretized_height;
+        falling_pair.lock_frames += 1;
+        falling_pair.fast || falling_pair.lock_frames > self.lock_delay
+    }
+
+    /// Puts the pair right away where it would land: each piece rests on its own column,
+    /// and the pair stops on the higher of the two. Returns whether there was a pair.
+    fn hard_drop_pair(&mut self) -> bool {
+        let Some(falling_pair) = self.pair.as_mut() else { return false };
+
+        let first = falling_pair.position;
+        let second = falling_pair.pair.get_second_position(first);
+        let row = [first, second]
+            .into_iter()
+            .map(|position| self.grid.column_top(position.col()) + first.row() - position.row())
+            .max()
+            .unwrap_or(first.row());
+        falling_pair.position = GridPosition::new(row, first.col());
+        falling_pair.height = row_height(row);
+        true
+    }
+
+    /// Moves every falling piece down by `speed` sub-cells.
+    fn fall_pieces(&mut self, speed: i32) {
+        // lower pieces first, so that the ones stacked on them land on the same frame
+        self.falling.sort_by_key(|piece| (piece.height, piece.col));
+
+        let mut still_falling = vec![];
+
+        for mut piece in self.falling.drain(..) {
+            piece.height -= speed;
+            let position = piece.position();
+            let discretized_height = row_height(position.row());
+
+            if self.grid.can_move_down(position) || piece.height >= discretized_height {
+                still_falling.push(piece);
+                continue;
+            }
+
+            // the column is full, the piece is lost above the grid
+            if !self.grid.is_valid(position) {
+                continue;
+            }
+
+            self.grid.place_cell(position, Some(piece.color));
+        }
+
+        self.falling = still_falling;
+    }
+
+    /// Looks for groups on the whole grid once everything has landed. Groups start popping,
+    /// otherwise the chain is over, with a bonus if it left the grid empty, and the next pair
+    /// spawns after the pending nuisance.
+    fn check_groups(&mut self, events: &mut BoardEvents) {
+        let groups: Vec<Vec<GridPosition>> = self
+            .grid
+            .groups()
+            .into_iter()
+            .filter(|group| group.len() >= MIN_GROUP_SIZE)
+            .collect();
+
+        if !groups.is_empty() {
+            let colors: Vec<(PieceColor, usize)> = groups
+                .iter()
+                .filter_map(|group| self.grid[group[0]].map(|color| (color, group.len())))
+                .collect();
+            events.links.push(self.score.add_link(&colors));
+
+            for group in groups {
+                self.popping.extend(self.grid.adjacent_nuisance(&group));
+                self.popping.extend(group);
+            }
+            self.popping.sort_by_key(|position| (position.row(), position.col()));
+            self.popping.dedup();
+            self.phase = Phase::Pop { frames: POP_FRAMES };
+            return;
+        }
+
+        if self.score.chain > 0 && self.grid.is_clear() {
+            self.score.add_all_clear();
+            self.all_clear = true;
+            events.all_clear = true;
+        }
+        self.score.reset_chain();
+        if !self.nuisance_dropped && self.nuisance.pending > 0 {
+            self.nuisance_dropped = true;
+            self.drop_nuisance();
+            return;
+        }
+
+        self.nuisance_dropped = false;
+        if !spawn_cells().into_iter().all(|cell| self.grid.is_empty(cell)) {
+            self.topped_out = true;
+            events.topped_out = true;
+            return;
+        }
+        self.spawn_pair();
+    }
+
+    /// Clears the popping cells, everything above them falls again.
+    fn pop(&mut self) {
+        let mut min_heights = vec![self.grid.height as isize; self.grid.width];
+        for position in self.popping.drain(..) {
+            self.grid[position] = None;
+            let col = position.col() as usize;
+            min_heights[col] = min(position.row(), min_heights[col]);
+        }
+
+        for (col, &min_height) in min_heights.iter().enumerate() {
+            for row in min_height..(self.grid.height as isize) {
+                let position = GridPosition::new(row, col as isize);
+
+                if let Some(color) = self.grid[position].take() {
+                    self.falling.push(FallingPiece::new(color, position));
+                }
+            }
+        }
+    }
+
+    /// Pending nuisance falls from above the grid once the chain is over, at the usual
+    /// speed even when no chain followed the tear.
+    fn drop_nuisance(&mut self) {
+        if self.nuisance.pending == 0 {
+            return;
+        }
+        self.phase = Phase::Fall;
+
+        for (col, count) in self.nuisance.take_drop(self.grid.width).into_iter().enumerate() {
+            for i in 0..count {
+                let position = GridPosition::new((self.grid.height + i) as isize, col as isize);
+                self.falling.push(FallingPiece::new(PieceColor::Nuisance, position));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{piece::PairOrientation, score::ALL_CLEAR_BONUS};
+
+    fn board() -> Board {
+        Board::new(Bag::new(0, 4), NuisanceQueue::new(0))
+    }
+
+    /// Steps until the current pair lands, returns the events of every frame.
+    fn drop_pair(board: &mut Board, input: PlayerInput) -> Vec<BoardEvents> {
+        let mut events = vec![];
+        loop {
+            let frame_events = board.step(input);
+            let landed = frame_events.pair_landed;
+            events.push(frame_events);
+            if landed {
+                return events;
+            }
+        }
+    }
+
+    /// Steps until the next pair spawns or the board tops out.
+    fn settle(board: &mut Board) -> Vec<BoardEvents> {
+        let mut events = vec![];
+        while board.phase != Phase::Control && !board.topped_out {
+            events.push(board.step(PlayerInput::default()));
+        }
+        events
+    }
+
+    #[test]
+    fn test_pair_lands_and_splits() {
+        let mut board = board();
+        let colors = board.pair.as_ref().unwrap().colors;
+
+        drop_pair(&mut board, PlayerInput { down: true, ..Default::default() });
+        assert!(board.falling.len() == 2);
+        // the next pair waits for the pieces to land
+        assert!(board.pair.is_none());
+        settle(&mut board);
+
+        assert!(board.grid[[0, STARTING_COL]] == Some(colors[1]));
+        assert!(board.grid[[1, STARTING_COL]] == Some(colors[0]));
+        assert!(board.pair.as_ref().unwrap().position == GridPosition::new(STARTING_ROW, STARTING_COL));
+    }
+
+    #[test]
+    fn test_move_and_rotate() {
+        let mut board = board();
+        let right = PlayerInput { right: true, ..Default::default() };
+        board.step(right);
+        assert!(board.pair.as_ref().unwrap().position.col() == STARTING_COL + 1);
+
+        // rotating only happens on the frame the button is pressed
+        let rotate = PlayerInput { rotate_clockwise: true, ..Default::default() };
+        board.step(rotate);
+        board.step(rotate);
+        assert!(board.pair.as_ref().unwrap().pair == Pair::new().turn_clockwise());
+    }
+
+    #[test]
+    fn test_hard_drop() {
+        let mut board = board();
+        for row in 0..3 {
+            board.grid[[row, STARTING_COL - 1]] = Some(PieceColor::Nuisance);
+        }
+        let colors = board.pair.as_ref().unwrap().colors;
+        board.step(PlayerInput { rotate_clockwise: true, ..Default::default() });
+
+        // the pair stops on the higher column and lands on the same frame
+        let drop = PlayerInput { hard_drop: true, ..Default::default() };
+        assert!(board.step(drop).pair_landed);
+        assert!(board.falling.iter().all(|piece| piece.height == row_height(3)));
+        settle(&mut board);
+        assert!(board.grid[[3, STARTING_COL - 1]] == Some(colors[1]));
+        assert!(board.grid[[0, STARTING_COL]] == Some(colors[0]));
+
+        // a button held since the last drop doesn't drop the next pair
+        assert!(board.step(drop).pair_landed);
+        while board.phase != Phase::Control {
+            board.step(drop);
+        }
+        assert!(!board.step(drop).pair_landed);
+    }
+
+    /// Steps without input until the pair rests on the stack.
+    fn ground_pair(board: &mut Board) {
+        while board.pair.as_ref().unwrap().lock_frames == 0 {
+            board.step(PlayerInput::default());
+        }
+    }
+
+    #[test]
+    fn test_quick_turn_on_stack() {
+        // a pair in a one-wide well flips both ways, lifted off the stack when it points up
+        let mut board = board();
+        for row in 0..=STARTING_ROW {
+            board.grid[[row, STARTING_COL - 1]] = Some(PieceColor::Nuisance);
+            board.grid[[row, STARTING_COL + 1]] = Some(PieceColor::Nuisance);
+        }
+        let rotate = PlayerInput { rotate_clockwise: true, ..Default::default() };
+        let flip = |board: &mut Board| {
+            for input in [rotate, PlayerInput::default(), rotate, PlayerInput::default()] {
+                board.step(input);
+            }
+        };
+
+        ground_pair(&mut board);
+        flip(&mut board);
+        assert!(board.pair.as_ref().unwrap().pair.orientation() == PairOrientation::BAVertical);
+        ground_pair(&mut board);
+        flip(&mut board);
+        let pair = board.pair.as_ref().unwrap();
+        assert!(pair.pair.orientation() == PairOrientation::ABVertical);
+        assert!(pair.position == GridPosition::new(1, STARTING_COL));
+    }
+
+    #[test]
+    fn test_lock_delay() {
+        let mut board = board();
+        ground_pair(&mut board);
+        let landed: Vec<bool> =
+            (0..LOCK_DELAY).map(|_| board.step(PlayerInput::default()).pair_landed).collect();
+        assert!(landed.iter().filter(|&&landed| landed).count() == 1);
+        assert!(landed[LOCK_DELAY as usize - 1]);
+
+        // pushing it down locks a grounded pair right away
+        settle(&mut board);
+        ground_pair(&mut board);
+        assert!(board.step(PlayerInput { down: true, ..Default::default() }).pair_landed);
+    }
+
+    #[test]
+    fn test_slide_on_ground() {
+        let mut board = board();
+        for row in 0..3 {
+            board.grid[[row, STARTING_COL]] = Some(PieceColor::Nuisance);
+        }
+        ground_pair(&mut board);
+
+        // the pair slides off the stack and falls again
+        board.step(PlayerInput { left: true, ..Default::default() });
+        board.step(PlayerInput::default());
+        let falling_pair = board.pair.as_ref().unwrap();
+        assert!(falling_pair.position.col() == STARTING_COL - 1);
+        assert!(falling_pair.lock_frames == 0);
+
+        // moving back and forth on the floor only holds it so long
+        ground_pair(&mut board);
+        let mut frames = 0;
+        loop {
+            let left = frames % 2 == 0;
+            let input = PlayerInput { left, right: !left, ..Default::default() };
+            if board.step(input).pair_landed {
+                break;
+            }
+            frames += 1;
+        }
+        assert!(frames > LOCK_DELAY && frames < LOCK_DELAY + LOCK_RESETS);
+    }
+
+    #[test]
+    fn test_tear() {
+        let mut board = board();
+        for row in 0..10 {
+            board.grid[[row, STARTING_COL]] = Some(PieceColor::Nuisance);
+        }
+        let colors = board.pair.as_ref().unwrap().colors;
+        board.step(PlayerInput { rotate_clockwise: true, ..Default::default() });
+        drop_pair(&mut board, PlayerInput { down: true, ..Default::default() });
+
+        // the half over the gap tears away, ten rows down faster than pieces fall after a pop
+        assert!(board.phase == Phase::Tear);
+        let mut frames = 0;
+        while board.phase == Phase::Tear {
+            assert!(board.pair.is_none());
+            board.step(PlayerInput::default());
+            frames += 1;
+        }
+        assert!(frames < 10 * SUBCELLS / FALL_SPEED / 2);
+        assert!(board.grid[[10, STARTING_COL]] == Some(colors[0]));
+        assert!(board.grid[[0, STARTING_COL - 1]] == Some(colors[1]));
+        assert!(board.pair.is_some());
+    }
+
+    #[test]
+    fn test_hidden_row() {
+        let mut board = board();
+        let top = GRID_HEIGHT as isize;
+        for row in 0..top {
+            let (even, odd) = (PieceColor::Yellow, PieceColor::Green);
+            board.grid[[row, 0]] = Some(if row % 2 == 0 { even } else { odd });
+            board.grid[[row, 1]] = Some(if row % 2 == 0 { odd } else { even });
+        }
+        // four in a column, but the top one is hidden
+        for row in top - 3..=top {
+            board.grid[[row, 1]] = Some(PieceColor::Red);
+        }
+        board.grid[[top, 0]] = Some(PieceColor::Red);
+        board.pair = None;
+        board.phase = Phase::Fall;
+        board.falling.push(FallingPiece::new(PieceColor::Blue, GridPosition::new(0, 2)));
+
+        assert!(settle(&mut board).iter().all(|events| events.links.is_empty()));
+        assert!(board.grid[[top, 1]] == Some(PieceColor::Red));
+        assert!(board.grid[[top, 0]] == Some(PieceColor::Red));
+    }
+
+    #[test]
+    fn test_all_clear() {
+        let mut board = board();
+        for col in 0..3 {
+            board.grid[[0, col]] = Some(PieceColor::Yellow);
+        }
+        board.pair = None;
+        board.phase = Phase::Fall;
+        board.falling.push(FallingPiece::new(PieceColor::Yellow, GridPosition::new(4, 3)));
+
+        let events = settle(&mut board);
+        assert!(events.iter().filter(|events| events.all_clear).count() == 1);
+        assert!(board.score.value == 40 + ALL_CLEAR_BONUS);
+        assert!(board.all_clear);
+
+        // the banner goes away with the next pair
+        drop_pair(&mut board, PlayerInput { down: true, ..Default::default() });
+        assert!(!board.all_clear);
+        assert!(!settle(&mut board).iter().any(|events| events.all_clear));
+    }
+
+    #[test]
+    fn test_nuisance_after_tear() {
+        let mut board = board();
+        board.nuisance.pending = 3;
+        drop_pair(&mut board, PlayerInput { down: true, ..Default::default() });
+        while board.falling.iter().all(|piece| piece.color != PieceColor::Nuisance) {
+            board.step(PlayerInput::default());
+        }
+
+        assert!(board.phase == Phase::Fall);
+        let height = board.falling[0].height;
+        board.step(PlayerInput::default());
+        assert!(board.falling.iter().all(|piece| piece.color == PieceColor::Nuisance));
+        assert!(board.falling[0].height == height - FALL_SPEED);
+    }
+
+    #[test]
+    fn test_chain() {
+        let mut board = board();
+        board.grid[[0, 0]] = Some(PieceColor::Blue);
+        for col in 1..4 {
+            board.grid[[0, col]] = Some(PieceColor::Red);
+            board.grid[[1, col]] = Some(PieceColor::Blue);
+        }
+        board.grid[[0, 9]] = Some(PieceColor::Nuisance);
+        board.pair = None;
+        board.phase = Phase::Fall;
+        board.falling.push(FallingPiece::new(PieceColor::Red, GridPosition::new(5, 4)));
+
+        // the reds pop, then the blues fall next to the last blue and pop as well
+        let events = settle(&mut board);
+        let links: Vec<u64> = events.iter().flat_map(|e| e.links.clone()).collect();
+        assert!(links == vec![40, 40 * 8]);
+        assert!(board.score.value == 40 + 40 * 8);
+        assert!(board.score.chain == 0);
+        assert!(board.grid[[0, 0]].is_none());
+
+        // each link stays on the grid while it pops
+        let link_frames: Vec<usize> = events
+            .iter()
+            .enumerate()
+            .filter_map(|(frame, e)| (!e.links.is_empty()).then_some(frame))
+            .collect();
+        assert!(link_frames[1] - link_frames[0] > POP_FRAMES as usize);
+    }
+
+    #[test]
+    fn test_pieces_landing_apart() {
+        let mut board = board();
+        board.pair = None;
+        board.phase = Phase::Fall;
+        // the group is only complete once the last piece lands, far behind the others
+        for col in 0..3 {
+            board.falling.push(FallingPiece::new(PieceColor::Green, GridPosition::new(0, col)));
+        }
+        board.falling.push(FallingPiece::new(PieceColor::Green, GridPosition::new(15, 3)));
+
+        let links: Vec<u64> = settle(&mut board).into_iter().flat_map(|e| e.links).collect();
+        assert!(links == vec![40]);
+        assert!(board.grid.groups().is_empty());
+    }
+
+    #[test]
+    fn test_game_over() {
+        let mut board = board();
+        for row in 0..STARTING_ROW - 1 {
+            board.grid[[row, STARTING_COL]] = Some(PieceColor::Nuisance);
+        }
+
+        let events = drop_pair(&mut board, PlayerInput::default());
+        assert!(!events.iter().any(|e| e.topped_out));
+        let events = settle(&mut board);
+        assert!(events.last().unwrap().topped_out);
+        assert!(board.topped_out);
+    }
+
+    #[test]
+    fn test_game_over_below_spawn() {
+        // the second piece spawns below the first one, a stack up to it is high enough
+        let mut board = board();
+        for row in 0..=STARTING_ROW - 1 {
+            board.grid[[row, STARTING_COL]] = Some(PieceColor::Nuisance);
+        }
+        board.pair = None;
+        board.phase = Phase::Fall;
+        assert!(settle(&mut board).last().unwrap().topped_out);
+        assert!(board.pair.is_none());
+    }
+}
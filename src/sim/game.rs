@@ -0,0 +1,229 @@
+use crate::sim::{
+    bag::Bag,
+    board::{Board, BoardEvents, LOCK_DELAY},
+    input::PlayerInput,
+    nuisance::NuisanceQueue,
+    score::ALL_CLEAR_BONUS,
+};
+
+/// Frames simulated per second.
+pub const FRAME_RATE: f64 = 64.;
+/// Most boards in a game, two players share the keyboard in versus.
+pub const MAX_PLAYERS: usize = 2;
+
+/// How a game ended.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Outcome {
+    /// The board that topped out first.
+    Loser(usize),
+    /// Several boards topped out on the same frame.
+    Draw,
+}
+
+/// Everything that decides how a game plays out, all boards share it.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Settings {
+    pub seed: u64,
+    pub classic_sequence: bool,
+    pub n_colors: usize,
+    pub players: usize,
+    /// Frames a grounded pair waits before it locks.
+    pub lock_delay: u32,
+}
+
+impl Default for Settings {
+    /// A single player with four colors, the seed is left to pick.
+    fn default() -> Self {
+        Self {
+            seed: 0,
+            classic_sequence: false,
+            n_colors: 4,
+            players: 1,
+            lock_delay: LOCK_DELAY,
+        }
+    }
+}
+
+/// All the boards of a game. In versus, chains on one board send nuisance to the others.
+#[derive(Clone)]
+pub struct Game {
+    pub settings: Settings,
+    pub boards: Vec<Board>,
+    /// Frames stepped since the game was created, restarts included.
+    pub frame: u64,
+}
+
+impl Game {
+    pub fn new(settings: &Settings) -> Self {
+        let boards = (0..settings.players)
+            .map(|_| {
+                // every board gets the same pairs
+                let bag = if settings.classic_sequence {
+                    Bag::classic(settings.seed, settings.n_colors)
+                } else {
+                    Bag::new(settings.seed, settings.n_colors)
+                };
+                let mut board = Board::new(bag, NuisanceQueue::new(settings.seed));
+                board.lock_delay = settings.lock_delay;
+                board
+            })
+            .collect();
+
+        Self {
+            settings: settings.clone(),
+            boards,
+            frame: 0,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        for board in self.boards.iter_mut() {
+            board.reset();
+        }
+    }
+
+    /// Advances every board by one frame, `inputs` has one entry per board. Nothing moves
+    /// anymore once a board has topped out.
+    pub fn step(&mut self, inputs: &[PlayerInput]) -> Vec<BoardEvents> {
+        if self.outcome().is_some() {
+            return vec![BoardEvents::default(); self.boards.len()];
+        }
+
+        let events: Vec<BoardEvents> = self
+            .boards
+            .iter_mut()
+            .zip(inputs)
+            .map(|(board, &input)| board.step(input))
+            .collect();
+        self.frame += 1;
+
+        for (player, board_events) in events.iter().enumerate() {
+            // an all clear sends nuisance on top of the chain's
+            let bonus = board_events.all_clear.then_some(ALL_CLEAR_BONUS);
+            for score in board_events.links.iter().copied().chain(bonus) {
+                let count = self.boards[player].nuisance.generate(score);
+                if count == 0 {
+                    continue;
+                }
+
+                for (other, board) in self.boards.iter_mut().enumerate() {
+                    if other != player {
+                        board.nuisance.pending += count;
+                    }
+                }
+            }
+        }
+
+        events
+    }
+
+    /// Whoever tops out first loses, boards that top out on the same frame draw. Nothing
+    /// moves once a board has topped out, so there is never more than one frame to look at.
+    pub fn outcome(&self) -> Option<Outcome> {
+        let mut topped_out = self.boards.iter().enumerate().filter(|(_, board)| board.topped_out);
+        match (topped_out.next(), topped_out.next()) {
+            (None, _) => None,
+            (Some((loser, _)), None) => Some(Outcome::Loser(loser)),
+            (Some(_), Some(_)) => Some(Outcome::Draw),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{
+        board::{FallingPiece, Phase, STARTING_COL, STARTING_ROW},
+        grid::GridPosition,
+        piece::PieceColor,
+    };
+
+    fn settings(players: usize) -> Settings {
+        Settings { seed: 42, players, ..Default::default() }
+    }
+
+    #[test]
+    fn test_deterministic() {
+        let inputs: Vec<PlayerInput> = (0..2000)
+            .map(|frame| PlayerInput {
+                left: frame % 90 < 10,
+                right: frame % 130 < 20,
+                down: frame % 50 < 30,
+                rotate_clockwise: frame % 17 == 0,
+                rotate_counter_clockwise: frame % 23 == 0,
+                hard_drop: frame % 150 < 2,
+            })
+            .collect();
+
+        let mut game1 = Game::new(&settings(1));
+        let mut game2 = Game::new(&settings(1));
+        for &input in &inputs {
+            assert!(game1.step(&[input]) == game2.step(&[input]));
+        }
+        assert!(game1.boards[0].grid == game2.boards[0].grid);
+    }
+
+    #[test]
+    fn test_send_nuisance() {
+        let mut game = Game::new(&settings(2));
+        for col in 0..3 {
+            game.boards[0].grid[[0, col]] = Some(PieceColor::Red);
+        }
+        game.boards[0].grid[[0, 9]] = Some(PieceColor::Nuisance);
+        game.boards[0].pair = None;
+        game.boards[0].phase = Phase::Fall;
+        game.boards[0].falling.push(FallingPiece::new(PieceColor::Red, GridPosition::new(0, 3)));
+        game.boards[0].nuisance.leftover_points = 30;
+        game.boards[1].nuisance.pending = 3;
+
+        let inputs = [PlayerInput::default(); 2];
+        while game.boards[0].phase != Phase::Control {
+            game.step(&inputs);
+        }
+
+        // the 40 points of the pop complete a nuisance piece
+        assert!(game.boards[1].nuisance.pending == 4);
+        assert!(game.boards[0].nuisance.leftover_points == 0);
+        assert!(game.outcome().is_none());
+
+        // emptying the grid sends the bonus as well
+        game.boards[0].grid[[0, 9]] = None;
+        for col in 0..3 {
+            game.boards[0].grid[[0, col]] = Some(PieceColor::Red);
+        }
+        game.boards[0].falling.push(FallingPiece::new(PieceColor::Red, GridPosition::new(0, 3)));
+        game.boards[0].pair = None;
+        game.boards[0].phase = Phase::Fall;
+        while game.boards[0].phase != Phase::Control {
+            game.step(&inputs);
+        }
+        let bonus = (40 + ALL_CLEAR_BONUS) as usize / 70;
+        assert!(game.boards[1].nuisance.pending == 4 + bonus);
+    }
+
+    #[test]
+    fn test_outcome() {
+        let mut game = Game::new(&settings(2));
+        // the pairs rest on a column up to the spawn, the next ones have no room
+        for board in game.boards.iter_mut() {
+            for row in 0..STARTING_ROW - 1 {
+                board.grid[[row, STARTING_COL]] = Some(PieceColor::Nuisance);
+            }
+        }
+
+        let drop = PlayerInput { hard_drop: true, ..Default::default() };
+        let mut inputs = [drop, PlayerInput::default()];
+        let mut both = game.clone();
+        while game.outcome().is_none() {
+            game.step(&inputs);
+        }
+        assert!(game.outcome() == Some(Outcome::Loser(0)));
+
+        // both top out on the same frame
+        inputs[1] = drop;
+        while both.outcome().is_none() {
+            both.step(&inputs);
+        }
+        assert!(both.outcome() == Some(Outcome::Draw));
+    }
+}
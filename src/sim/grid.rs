@@ -0,0 +1,468 @@
+use std::{
+    collections::HashSet,
+    ops::{Index, IndexMut},
+};
+
+use crate::sim::piece::{Pair, PieceColor};
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Grid<T> {
+    pub height: usize,
+    pub width: usize,
+    /// Rows of the visible field, the ones above are hidden: pieces rest there but never pop.
+    pub visible_height: usize,
+    data: Vec<T>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct GridPosition {
+    value: [isize; 2],
+}
+
+impl GridPosition {
+    pub fn new(row: isize, col: isize) -> Self {
+        Self { value: [row, col] }
+    }
+
+    pub fn row(self) -> isize {
+        self.value[0]
+    }
+
+    pub fn col(self) -> isize {
+        self.value[1]
+    }
+
+    pub fn translate(&self, row: isize, col: isize) -> Self {
+        Self {
+            value: [self.row() + row, self.col() + col],
+        }
+    }
+}
+
+impl<T> Grid<T> {
+    pub fn new(height: usize, width: usize, data: Vec<T>) -> Self {
+        assert!(width * height == data.len());
+        Self {
+            height,
+            width,
+            visible_height: height,
+            data,
+        }
+    }
+
+    /// The same grid with only its `visible_height` bottom rows on the field.
+    pub fn with_visible_height(mut self, visible_height: usize) -> Self {
+        assert!(visible_height <= self.height);
+        self.visible_height = visible_height;
+        self
+    }
+
+    /// Whether a cell is above the visible field, where pieces rest but never pop.
+    pub fn is_hidden(&self, grid_position: GridPosition) -> bool {
+        grid_position.row() >= self.visible_height as isize
+    }
+
+    pub fn place_cell(&mut self, grid_position: GridPosition, value: T) {
+        self[grid_position.value] = value;
+    }
+}
+
+impl<T> Grid<Option<T>> {
+    pub fn is_valid(&self, grid_position: GridPosition) -> bool {
+        grid_position.row() < self.height as isize
+            && grid_position.col() < self.width as isize
+            && grid_position.row() >= 0
+            && grid_position.col() >= 0
+    }
+
+    pub fn is_empty(&self, grid_position: GridPosition) -> bool {
+        self.is_valid(grid_position) && (self[grid_position.value]).is_none()
+    }
+
+    pub fn clear(&mut self) {
+        self.data.iter_mut().for_each(|cell| *cell = None);
+    }
+
+    pub fn is_clear(&self) -> bool {
+        self.data.iter().all(Option::is_none)
+    }
+
+    /// Lowest empty row of a column, the grid's height when the column is full.
+    pub fn column_top(&self, col: isize) -> isize {
+        (0..self.height as isize)
+            .find(|&row| self.is_empty(GridPosition::new(row, col)))
+            .unwrap_or(self.height as isize)
+    }
+
+    pub fn can_move_left(&self, grid_position: GridPosition) -> bool {
+        self.is_empty(grid_position.translate(0, -1))
+    }
+
+    pub fn can_move_right(&self, grid_position: GridPosition) -> bool {
+        self.is_empty(grid_position.translate(0, 1))
+    }
+
+    /// Cells above the top of the grid are open, so that pieces can fall in from there.
+    pub fn is_above(&self, grid_position: GridPosition) -> bool {
+        grid_position.row() >= self.height as isize
+            && grid_position.col() < self.width as isize
+            && grid_position.col() >= 0
+    }
+
+    pub fn can_move_down(&self, grid_position: GridPosition) -> bool {
+        let below = grid_position.translate(-1, 0);
+        self.is_empty(below) || self.is_above(below)
+    }
+
+    /// Moves the pair one column to the right if there is room, returns whether it moved.
+    pub fn move_right_pair(&self, pair: Pair, grid_position: &mut GridPosition) -> bool {
+        let can_move = self.can_move_right(*grid_position)
+            && self.can_move_right(pair.get_second_position(*grid_position));
+        if can_move {
+            grid_position.value[1] += 1;
+        }
+        can_move
+    }
+
+    /// Moves the pair one column to the left if there is room, returns whether it moved.
+    pub fn move_left_pair(&self, pair: Pair, grid_position: &mut GridPosition) -> bool {
+        let can_move = self.can_move_left(*grid_position)
+            && self.can_move_left(pair.get_second_position(*grid_position));
+        if can_move {
+            grid_position.value[1] -= 1;
+        }
+        can_move
+    }
+
+    pub fn can_move_down_pair(&self, pair: Pair, grid_position: GridPosition) -> bool {
+        self.can_move_down(grid_position) && self.can_move_down(pair.get_second_position(grid_position))
+    }
+
+    /// Where the pair ends up after turning into `new_pair`. When the second piece would
+    /// land on a wall, the floor or the stack, the pair is kicked one cell the other way.
+    fn kick_position(&self, new_pair: Pair, grid_position: GridPosition) -> Option<GridPosition> {
+        let second_position = new_pair.get_second_position(grid_position);
+        if self.is_empty(second_position) {
+            return Some(grid_position);
+        }
+
+        let kicked_position = grid_position.translate(
+            grid_position.row() - second_position.row(),
+            grid_position.col() - second_position.col(),
+        );
+        let can_kick = self.is_empty(kicked_position)
+            && self.is_empty(new_pair.get_second_position(kicked_position));
+        can_kick.then_some(kicked_position)
+    }
+
+    pub fn turn_clockwise_position(&self, pair: Pair, grid_position: GridPosition) -> Option<GridPosition> {
+        self.kick_position(pair.turn_clockwise(), grid_position)
+    }
+
+    pub fn turn_counter_clockwise_position(
+        &self,
+        pair: Pair,
+        grid_position: GridPosition,
+    ) -> Option<GridPosition> {
+        self.kick_position(pair.turn_counter_clockwise(), grid_position)
+    }
+
+    pub fn can_turn_clockwise(&self, pair: Pair, grid_position: GridPosition) -> bool {
+        self.turn_clockwise_position(pair, grid_position).is_some()
+    }
+
+    pub fn can_turn_counter_clockwise(&self, pair: Pair, grid_position: GridPosition) -> bool {
+        self.turn_counter_clockwise_position(pair, grid_position).is_some()
+    }
+
+    /// Where the pair ends up after a quick turn, which is only allowed when the pair is
+    /// stuck between two walls or stacks. A pair flipped into the stack is lifted a row, the
+    /// same kick as for a turn.
+    pub fn quick_turn_position(&self, pair: Pair, grid_position: GridPosition) -> Option<GridPosition> {
+        let stuck = !self.can_turn_clockwise(pair, grid_position)
+            && !self.can_turn_counter_clockwise(pair, grid_position);
+        stuck.then(|| self.kick_position(pair.quick_turn(), grid_position)).flatten()
+    }
+
+    pub fn can_quick_turn(&self, pair: Pair, grid_position: GridPosition) -> bool {
+        self.quick_turn_position(pair, grid_position).is_some()
+    }
+}
+
+impl<T> Index<[isize; 2]> for Grid<T> {
+    type Output = T;
+    fn index(&self, index: [isize; 2]) -> &T {
+        let row = index[0] as usize;
+        let col = index[1] as usize;
+        &self.data[self.width * row + col]
+    }
+}
+
+impl<T> IndexMut<[isize; 2]> for Grid<T> {
+    fn index_mut(&mut self, index: [isize; 2]) -> &mut T {
+        let row = index[0] as usize;
+        let col = index[1] as usize;
+        &mut self.data[self.width * row + col]
+    }
+}
+
+impl<T> Index<GridPosition> for Grid<T> {
+    type Output = T;
+    fn index(&self, index: GridPosition) -> &T {
+        &self[index.value]
+    }
+}
+
+impl<T> IndexMut<GridPosition> for Grid<T> {
+    fn index_mut(&mut self, index: GridPosition) -> &mut T {
+        &mut self[index.value]
+    }
+}
+
+fn get_adjacent(position: GridPosition) -> Vec<GridPosition> {
+    vec![
+        position.translate(1, 0),
+        position.translate(-1, 0),
+        position.translate(0, 1),
+        position.translate(0, -1),
+    ]
+}
+
+fn add_position(
+    position: GridPosition,
+    conn_comp: &mut Vec<GridPosition>,
+    adjacent: &mut Vec<GridPosition>,
+    seen: &mut HashSet<GridPosition>,
+) {
+    conn_comp.push(position);
+    for p in get_adjacent(position).into_iter() {
+        if !seen.contains(&p) {
+            seen.insert(p);
+            adjacent.push(p);
+        }
+    }
+}
+
+impl Grid<Option<PieceColor>> {
+    pub fn find_conn_comp(&self, initial_position: GridPosition) -> Vec<GridPosition> {
+        let initial_color = match self[initial_position] {
+            None => return vec![],
+            Some(PieceColor::Nuisance) => return vec![initial_position],
+            Some(color) => color,
+        };
+
+        let mut conn_comp: Vec<GridPosition> = vec![initial_position];
+
+        let mut adjacent = get_adjacent(initial_position);
+        let mut seen = HashSet::from_iter(adjacent.clone());
+        seen.insert(initial_position);
+
+        while let Some(position) = adjacent.pop() {
+            if !self.is_valid(position) { continue }
+            if let Some(color) = self[position] {
+                if color == initial_color {
+                    add_position(position, &mut conn_comp, &mut adjacent, &mut seen);
+                }
+            }
+        }
+
+        conn_comp
+    }
+
+    /// Every group of pieces of the same color, nuisance excluded. Pieces in the hidden
+    /// rows never connect.
+    pub fn groups(&self) -> Vec<Vec<GridPosition>> {
+        let mut seen = vec![false; self.data.len()];
+        let mut groups = vec![];
+
+        for row in 0..self.visible_height as isize {
+            for col in 0..self.width as isize {
+                let index = self.width * row as usize + col as usize;
+                match self[[row, col]] {
+                    None | Some(PieceColor::Nuisance) => continue,
+                    Some(_) if seen[index] => continue,
+                    Some(_) => (),
+                }
+
+                let group = self.flood(GridPosition::new(row, col), &mut seen);
+                groups.push(group);
+            }
+        }
+        groups
+    }
+
+    /// The group of `start`, marking its cells in `seen`. Unlike `find_conn_comp` it needs
+    /// no hashing, which matters when every group of the grid is looked for.
+    fn flood(&self, start: GridPosition, seen: &mut [bool]) -> Vec<GridPosition> {
+        let color = self[start];
+        let mut group = vec![];
+        let mut stack = vec![start];
+        seen[self.width * start.row() as usize + start.col() as usize] = true;
+
+        while let Some(position) = stack.pop() {
+            group.push(position);
+            for neighbour in get_adjacent(position) {
+                if !self.is_valid(neighbour) || self.is_hidden(neighbour) || self[neighbour] != color {
+                    continue;
+                }
+                let index = self.width * neighbour.row() as usize + neighbour.col() as usize;
+                if !seen[index] {
+                    seen[index] = true;
+                    stack.push(neighbour);
+                }
+            }
+        }
+        group
+    }
+
+    /// Nuisance pieces touching a group, which are cleared along with it unless they are
+    /// hidden.
+    pub fn adjacent_nuisance(&self, conn_comp: &[GridPosition]) -> Vec<GridPosition> {
+        let mut nuisance = HashSet::new();
+
+        for &position in conn_comp {
+            for p in get_adjacent(position) {
+                if self.is_valid(p) && !self.is_hidden(p) && self[p] == Some(PieceColor::Nuisance) {
+                    nuisance.insert(p);
+                }
+            }
+        }
+
+        let mut nuisance: Vec<GridPosition> = nuisance.into_iter().collect();
+        nuisance.sort_by_key(|p| (p.row(), p.col()));
+        nuisance
+    }
+}
+
+pub type GameGrid = Grid<Option<PieceColor>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_index() {
+        let grid = Grid::new(2, 2, vec![1, 2, 3, 4]);
+
+        assert!(grid[[0, 1]] == 2);
+        assert!(grid[[1, 0]] == 3);
+    }
+
+    #[test]
+    fn test_move_right() {
+        let grid: Grid<Option<u8>> = Grid::new(2, 2, vec![None; 4]);
+        let grid_position1 = GridPosition::new(0, 0);
+        let grid_position2 = GridPosition::new(0, 1);
+
+        assert!(grid.can_move_right(grid_position1));
+        assert!(!grid.can_move_right(grid_position2));
+    }
+
+    #[test]
+    fn test_quick_turn() {
+        let mut grid: Grid<Option<u8>> = Grid::new(4, 3, vec![None; 12]);
+        let position = GridPosition::new(2, 1);
+        let pair = Pair::new();
+
+        assert!(grid.can_turn_clockwise(pair, position));
+        assert!(!grid.can_quick_turn(pair, position));
+
+        for row in 0..3 {
+            grid[[row, 0]] = Some(0);
+            grid[[row, 2]] = Some(0);
+        }
+
+        assert!(!grid.can_turn_clockwise(pair, position));
+        assert!(!grid.can_turn_counter_clockwise(pair, position));
+        assert!(grid.quick_turn_position(pair, position) == Some(position));
+        assert!(pair.quick_turn().get_second_position(position) == GridPosition::new(3, 1));
+
+        // an upward pair resting on the stack is lifted a row as it flips
+        let pair = pair.quick_turn();
+        let position = GridPosition::new(1, 1);
+        grid[[0, 1]] = Some(0);
+        assert!(grid.quick_turn_position(pair, position) == Some(GridPosition::new(2, 1)));
+    }
+
+    #[test]
+    fn test_kicks() {
+        let grid: Grid<Option<u8>> = Grid::new(4, 3, vec![None; 12]);
+        let pair = Pair::new();
+
+        // second piece would go past the left wall
+        let position = GridPosition::new(2, 0);
+        assert!(grid.turn_clockwise_position(pair, position) == Some(GridPosition::new(2, 1)));
+        assert!(grid.turn_counter_clockwise_position(pair, position) == Some(position));
+
+        // second piece would go below the floor
+        let pair = pair.turn_clockwise();
+        let position = GridPosition::new(0, 1);
+        assert!(grid.turn_counter_clockwise_position(pair, position) == Some(GridPosition::new(1, 1)));
+    }
+
+    #[test]
+    fn test_conn_comp() {
+        let mut grid: GameGrid = Grid::new(2, 2, vec![None; 4]);
+        grid[[0, 0]] = Some(PieceColor::Red);
+        grid[[0, 1]] = Some(PieceColor::Red);
+        grid[[1, 0]] = Some(PieceColor::Blue);
+        let initial_position = GridPosition::new(0, 0);
+
+        let expected = vec![GridPosition::new(0, 0), GridPosition::new(0, 1)];
+
+        let output = grid.find_conn_comp(initial_position);
+
+        assert!(expected == output)
+
+    }
+
+    #[test]
+    fn test_nuisance() {
+        let mut grid: GameGrid = Grid::new(3, 3, vec![None; 9]);
+        grid[[0, 0]] = Some(PieceColor::Red);
+        grid[[0, 1]] = Some(PieceColor::Red);
+        grid[[0, 2]] = Some(PieceColor::Nuisance);
+        grid[[1, 0]] = Some(PieceColor::Nuisance);
+        grid[[1, 1]] = Some(PieceColor::Nuisance);
+        grid[[2, 0]] = Some(PieceColor::Nuisance);
+
+        // nuisance never forms groups
+        assert!(grid.find_conn_comp(GridPosition::new(1, 0)) == vec![GridPosition::new(1, 0)]);
+
+        let conn_comp = grid.find_conn_comp(GridPosition::new(0, 0));
+        let mut nuisance = grid.adjacent_nuisance(&conn_comp);
+        nuisance.sort_by_key(|p| (p.row(), p.col()));
+
+        let expected = vec![GridPosition::new(0, 2), GridPosition::new(1, 0), GridPosition::new(1, 1)];
+        assert!(nuisance == expected);
+    }
+
+    #[test]
+    fn test_groups() {
+        let mut grid: GameGrid = Grid::new(2, 3, vec![None; 6]);
+        grid[[0, 0]] = Some(PieceColor::Red);
+        grid[[1, 0]] = Some(PieceColor::Red);
+        grid[[1, 1]] = Some(PieceColor::Red);
+        grid[[0, 1]] = Some(PieceColor::Blue);
+        grid[[0, 2]] = Some(PieceColor::Nuisance);
+
+        let mut sizes: Vec<usize> = grid.groups().iter().map(Vec::len).collect();
+        sizes.sort();
+        assert!(sizes == vec![1, 3]);
+    }
+
+    #[test]
+    fn test_hidden_row() {
+        let mut grid: GameGrid = Grid::new(5, 1, vec![None; 5]).with_visible_height(4);
+        for row in 1..5 {
+            grid[[row, 0]] = Some(PieceColor::Green);
+        }
+        let top = GridPosition::new(4, 0);
+        assert!(grid.is_hidden(top));
+        assert!(grid.groups().iter().map(Vec::len).collect::<Vec<_>>() == vec![3]);
+
+        grid[top] = Some(PieceColor::Nuisance);
+        let group = grid.groups().remove(0);
+        assert!(grid.adjacent_nuisance(&group).is_empty());
+    }
+}
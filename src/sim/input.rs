@@ -0,0 +1,163 @@
+/// Frames a direction has to be held before it starts repeating.
+const START_DELAY: u32 = 6;
+/// Frames between two repeated moves.
+const REPEAT_DELAY: u32 = 2;
+/// Frames after a refused rotation during which a second one flips the pair.
+const QUICK_TURN_WINDOW: u32 = 19;
+
+/// Buttons held by a player during one frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct PlayerInput {
+    pub left: bool,
+    pub right: bool,
+    pub down: bool,
+    pub rotate_clockwise: bool,
+    pub rotate_counter_clockwise: bool,
+    pub hard_drop: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Shift {
+    Left,
+    Right,
+}
+
+impl PlayerInput {
+    /// Right wins when both directions are held.
+    pub fn shift(self) -> Option<Shift> {
+        if self.right {
+            Some(Shift::Right)
+        } else if self.left {
+            Some(Shift::Left)
+        } else {
+            None
+        }
+    }
+}
+
+/// Delayed auto shift: a held direction moves once, then repeats after a delay.
+#[derive(Clone)]
+pub struct DASTimer {
+    start_timer: u32,
+    repeat_timer: u32,
+    repeat_delay: u32,
+    start_delay: u32,
+    shift: Option<Shift>,
+}
+
+impl Default for DASTimer {
+    fn default() -> Self {
+        Self::new(REPEAT_DELAY, START_DELAY)
+    }
+}
+
+impl DASTimer {
+    pub fn new(repeat_delay: u32, start_delay: u32) -> Self {
+        Self {
+            repeat_delay,
+            start_delay,
+            start_timer: 0,
+            repeat_timer: 0,
+            shift: None,
+        }
+    }
+
+    pub fn reset(&mut self) {
+        self.start_timer = 0;
+        self.repeat_timer = 0;
+        self.shift = None;
+    }
+
+    fn can_repeat(&mut self) -> bool {
+        if self.start_timer >= self.start_delay {
+            self.repeat_timer += 1;
+            if self.repeat_timer >= self.repeat_delay {
+                self.repeat_timer = 0;
+                true
+            } else {
+                false
+            }
+        } else {
+            self.start_timer += 1;
+            false
+        }
+    }
+
+    /// Advances one frame with the direction currently held, returns the move to make.
+    pub fn update(&mut self, new_shift: Option<Shift>) -> Option<Shift> {
+        match (self.shift, new_shift) {
+            (None, None) => None,
+            (Some(_), None) => {
+                self.reset();
+                None
+            }
+            (Some(shift1), Some(shift2)) if shift1 == shift2 => {
+                self.can_repeat().then_some(shift1)
+            }
+            (_, Some(shift)) => {
+                self.reset();
+                self.shift = Some(shift);
+                Some(shift)
+            }
+        }
+    }
+}
+
+/// Remembers a refused rotation for a short time, so that a second press flips the pair.
+#[derive(Clone)]
+pub struct QuickTurnTimer {
+    window: u32,
+    elapsed: Option<u32>,
+}
+
+impl Default for QuickTurnTimer {
+    fn default() -> Self {
+        Self::new(QUICK_TURN_WINDOW)
+    }
+}
+
+impl QuickTurnTimer {
+    pub fn new(window: u32) -> Self {
+        Self {
+            window,
+            elapsed: None,
+        }
+    }
+
+    pub fn tick(&mut self) {
+        if let Some(elapsed) = self.elapsed.as_mut() {
+            *elapsed += 1;
+        }
+    }
+
+    pub fn arm(&mut self) {
+        self.elapsed = Some(0);
+    }
+
+    pub fn reset(&mut self) {
+        self.elapsed = None;
+    }
+
+    pub fn is_armed(&self) -> bool {
+        self.elapsed.is_some_and(|elapsed| elapsed <= self.window)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_das() {
+        let mut timer = DASTimer::new(2, 3);
+        let moves: Vec<Option<Shift>> = (0..8).map(|_| timer.update(Some(Shift::Left))).collect();
+
+        assert!(moves[0] == Some(Shift::Left));
+        assert!(moves[1..4].iter().all(Option::is_none));
+        assert!(moves[4..] == [None, Some(Shift::Left), None, Some(Shift::Left)]);
+
+        // changing direction moves right away
+        assert!(timer.update(Some(Shift::Right)) == Some(Shift::Right));
+        assert!(timer.update(None).is_none());
+    }
+}
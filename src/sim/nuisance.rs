@@ -0,0 +1,84 @@
+use rand::{rngs::StdRng, seq::index::sample, SeedableRng};
+
+/// At most this many rows of nuisance fall at once, the rest waits for the next pair.
+const MAX_NUISANCE_ROWS: usize = 5;
+/// Points a chain has to score to generate one nuisance piece.
+const NUISANCE_TARGET_POINTS: u64 = 70;
+
+/// Nuisance waiting to fall on a board, and where the incomplete rows go.
+#[derive(Clone)]
+pub struct NuisanceQueue {
+    pub pending: usize,
+    /// Points that did not add up to a whole nuisance piece yet.
+    pub leftover_points: u64,
+    rng: StdRng,
+}
+
+impl NuisanceQueue {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            pending: 0,
+            leftover_points: 0,
+            rng: StdRng::seed_from_u64(seed),
+        }
+    }
+
+    /// Turns the points of a chain link into nuisance, which first offsets the nuisance
+    /// pending on this board. Returns what is left to send to the opponents.
+    pub fn generate(&mut self, points: u64) -> usize {
+        let points = points + self.leftover_points;
+        self.leftover_points = points % NUISANCE_TARGET_POINTS;
+        let count = (points / NUISANCE_TARGET_POINTS) as usize;
+
+        let offset = count.min(self.pending);
+        self.pending -= offset;
+        count - offset
+    }
+
+    /// Takes as much pending nuisance as can fall at once and spreads it across the columns:
+    /// full rows first, then the remainder in distinct random columns.
+    pub fn take_drop(&mut self, width: usize) -> Vec<usize> {
+        let count = self.pending.min(MAX_NUISANCE_ROWS * width);
+        self.pending -= count;
+
+        let mut columns = vec![count / width; width];
+        for col in sample(&mut self.rng, width, count % width) {
+            columns[col] += 1;
+        }
+        columns
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_drop() {
+        let mut queue = NuisanceQueue::new(0);
+        queue.pending = 23;
+
+        let columns = queue.take_drop(10);
+        assert!(columns.iter().sum::<usize>() == 23);
+        assert!(columns.iter().all(|&count| count == 2 || count == 3));
+        assert!(queue.pending == 0);
+
+        queue.pending = 80;
+        assert!(queue.take_drop(10) == vec![MAX_NUISANCE_ROWS; 10]);
+        assert!(queue.pending == 80 - 10 * MAX_NUISANCE_ROWS);
+    }
+
+    #[test]
+    fn test_generate() {
+        let mut queue = NuisanceQueue::new(0);
+
+        assert!(queue.generate(40) == 0);
+        assert!(queue.generate(40) == 1);
+        assert!(queue.leftover_points == 10);
+
+        // offsetting
+        queue.pending = 3;
+        assert!(queue.generate(5 * NUISANCE_TARGET_POINTS) == 2);
+        assert!(queue.pending == 0);
+    }
+}
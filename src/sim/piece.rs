@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+
+use crate::sim::grid::GridPosition;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PieceColor {
+    Red,
+    Blue,
+    Purple,
+    Green,
+    Yellow,
+    Cyan,
+    /// Never part of a group, cleared when an adjacent group pops.
+    Nuisance,
+}
+
+impl PieceColor {
+    /// Every color that forms groups, in the order in which game modes with fewer colors
+    /// pick them.
+    pub const ALL: [PieceColor; 6] = [
+        PieceColor::Red,
+        PieceColor::Blue,
+        PieceColor::Purple,
+        PieceColor::Green,
+        PieceColor::Yellow,
+        PieceColor::Cyan,
+    ];
+}
+
+/// Where the second piece of a pair is: below, right of, above or left of the first one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PairOrientation {
+    #[serde(rename = "ab_vertical")]
+    ABVertical,
+    #[serde(rename = "ab_horizontal")]
+    ABHorizontal,
+    #[serde(rename = "ba_vertical")]
+    BAVertical,
+    #[serde(rename = "ba_horizontal")]
+    BAHorizontal,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Pair {
+    orientation: PairOrientation,
+}
+
+impl Default for Pair {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Pair {
+    pub fn new() -> Self {
+        Pair {
+            orientation: PairOrientation::ABVertical,
+        }
+    }
+
+    pub fn with_orientation(orientation: PairOrientation) -> Self {
+        Pair { orientation }
+    }
+
+    pub fn orientation(self) -> PairOrientation {
+        self.orientation
+    }
+
+    pub fn get_second_position(self, position: GridPosition) -> GridPosition {
+        match self.orientation {
+            PairOrientation::ABVertical => position.translate(-1, 0),
+            PairOrientation::BAVertical => position.translate(1, 0),
+            PairOrientation::ABHorizontal => position.translate(0, 1),
+            PairOrientation::BAHorizontal => position.translate(0, -1),
+        }
+    }
+
+    pub fn turn_clockwise(self) -> Self {
+        let new_orientation = match self.orientation {
+            PairOrientation::ABVertical => PairOrientation::BAHorizontal,
+            PairOrientation::BAHorizontal => PairOrientation::BAVertical,
+            PairOrientation::BAVertical => PairOrientation::ABHorizontal,
+            PairOrientation::ABHorizontal => PairOrientation::ABVertical,
+        };
+
+        Pair {
+            orientation: new_orientation,
+        }
+    }
+
+    pub fn turn_counter_clockwise(self) -> Self {
+        let new_orientation = match self.orientation {
+            PairOrientation::ABVertical => PairOrientation::ABHorizontal,
+            PairOrientation::ABHorizontal => PairOrientation::BAVertical,
+            PairOrientation::BAVertical => PairOrientation::BAHorizontal,
+            PairOrientation::BAHorizontal => PairOrientation::ABVertical,
+        };
+
+        Pair {
+            orientation: new_orientation,
+        }
+    }
+
+    /// Flips the pair 180 degrees around its first piece.
+    pub fn quick_turn(self) -> Self {
+        self.turn_clockwise().turn_clockwise()
+    }
+}
@@ -0,0 +1,354 @@
+use std::collections::{HashSet, VecDeque};
+
+use serde::{Deserialize, Serialize};
+
+use crate::sim::{
+    board::{Board, FallingPair, FallingPiece, STARTING_COL, STARTING_ROW},
+    grid::{GameGrid, GridPosition},
+    input::PlayerInput,
+    piece::{Pair, PairOrientation, PieceColor},
+};
+
+const ORIENTATIONS: [PairOrientation; 4] = [
+    PairOrientation::ABVertical,
+    PairOrientation::ABHorizontal,
+    PairOrientation::BAVertical,
+    PairOrientation::BAHorizontal,
+];
+
+/// Where a pair is dropped: the column of its first piece and where the second one is.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Placement {
+    pub col: isize,
+    pub orientation: PairOrientation,
+}
+
+/// A placement along with the buttons that take a pair there.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Move {
+    pub placement: Placement,
+    /// Buttons held on every frame, from now to the frame the pair lands.
+    pub inputs: Vec<PlayerInput>,
+    /// Cells where the first and the second piece come to rest.
+    pub cells: [GridPosition; 2],
+}
+
+/// The turn that brings `pair` closer to `orientation`, the same one `Placement::input` makes.
+fn turns_clockwise(pair: Pair, orientation: PairOrientation) -> bool {
+    pair.turn_counter_clockwise().orientation() != orientation
+}
+
+/// Buttons tried on every frame of the search in `moves`. Holding down only makes the pair
+/// land sooner, which the hard drop does right away.
+fn search_inputs() -> [PlayerInput; 6] {
+    [
+        PlayerInput::default(),
+        PlayerInput { left: true, ..Default::default() },
+        PlayerInput { right: true, ..Default::default() },
+        PlayerInput { rotate_clockwise: true, ..Default::default() },
+        PlayerInput { rotate_counter_clockwise: true, ..Default::default() },
+        PlayerInput { hard_drop: true, ..Default::default() },
+    ]
+}
+
+/// Where the pair of `pieces`, the first piece then the second one, lies.
+fn placement_of(pieces: &[FallingPiece]) -> Option<Placement> {
+    let [first, second] = [pieces.first()?.position(), pieces.get(1)?.position()];
+    ORIENTATIONS
+        .into_iter()
+        .find(|&orientation| Pair::with_orientation(orientation).get_second_position(first) == second)
+        .map(|orientation| Placement { col: first.col(), orientation })
+}
+
+/// Every placement the current pair of `board` can come to rest in, with the inputs that
+/// take it there. The search steps copies of the board one frame and one button at a time,
+/// breadth first, so the moves follow the movement rules, slides and turns during the lock
+/// delay included, and their inputs are among the shortest.
+pub fn moves(board: &Board) -> Vec<Move> {
+    if board.pair.is_none() {
+        return vec![];
+    }
+
+    let mut moves: Vec<Move> = vec![];
+    let mut seen = HashSet::new();
+    let mut queue = VecDeque::from([(board.clone(), vec![])]);
+    while let Some((state, inputs)) = queue.pop_front() {
+        for input in search_inputs() {
+            let mut next = state.clone();
+            let events = next.step(input);
+            let mut next_inputs: Vec<PlayerInput> = inputs.clone();
+            next_inputs.push(input);
+
+            if events.pair_landed {
+                let Some(placement) = placement_of(&next.falling) else { continue };
+                if !moves.iter().any(|m| m.placement == placement) {
+                    moves.push(Move {
+                        placement,
+                        inputs: next_inputs,
+                        cells: placement.cells(&board.grid),
+                    });
+                }
+                continue;
+            }
+
+            let Some(pair) = next.pair.as_ref() else { continue };
+            // states are met in the order of the frames, the first time the pair is in a
+            // cell it is the highest it will be there, with the most time left to move
+            let key = (
+                pair.position,
+                pair.pair.orientation(),
+                pair.lock_frames > 0,
+                input,
+                next.quick_turn_armed(),
+            );
+            if seen.insert(key) {
+                queue.push_back((next, next_inputs));
+            }
+        }
+    }
+    moves
+}
+
+impl Placement {
+    /// Every placement a pair reaches from the spawn by turning, then shifting, assuming
+    /// it stays at the spawn row while it does. Cheaper than `moves` but only close to the
+    /// movement rules.
+    pub fn reachable(grid: &GameGrid) -> Vec<Placement> {
+        let spawn = GridPosition::new(STARTING_ROW, STARTING_COL);
+        let mut placements = vec![];
+
+        for orientation in ORIENTATIONS {
+            let (mut pair, mut position) = (Pair::new(), spawn);
+            while pair.orientation() != orientation {
+                let turn = if turns_clockwise(pair, orientation) {
+                    grid.turn_clockwise_position(pair, position)
+                        .map(|position| (pair.turn_clockwise(), position))
+                } else {
+                    grid.turn_counter_clockwise_position(pair, position)
+                        .map(|position| (pair.turn_counter_clockwise(), position))
+                };
+                match turn {
+                    Some(turn) => (pair, position) = turn,
+                    None => match grid.quick_turn_position(pair, position) {
+                        Some(new_position) => (pair, position) = (pair.quick_turn(), new_position),
+                        None => break,
+                    },
+                }
+            }
+            if pair.orientation() != orientation {
+                continue;
+            }
+
+            placements.push(Placement { col: position.col(), orientation });
+            for step in [-1, 1] {
+                let mut shifted = position;
+                loop {
+                    let moved = if step < 0 {
+                        grid.move_left_pair(pair, &mut shifted)
+                    } else {
+                        grid.move_right_pair(pair, &mut shifted)
+                    };
+                    if !moved {
+                        break;
+                    }
+                    placements.push(Placement { col: shifted.col(), orientation });
+                }
+            }
+        }
+
+        placements
+    }
+
+    /// Buttons to hold this frame to bring `falling_pair` here: turns first, then shifts,
+    /// then down. Buttons are released every other frame since turns and shifts happen
+    /// when they are pressed. A placement that can't be reached is tried until the pair
+    /// lands wherever it is.
+    pub fn input(self, falling_pair: &FallingPair, previous: PlayerInput) -> PlayerInput {
+        let mut input = PlayerInput::default();
+        let pair = falling_pair.pair;
+        let col = falling_pair.position.col();
+
+        if pair.orientation() != self.orientation {
+            if !previous.rotate_clockwise && !previous.rotate_counter_clockwise {
+                input.rotate_clockwise = turns_clockwise(pair, self.orientation);
+                input.rotate_counter_clockwise = !input.rotate_clockwise;
+            }
+        } else if col != self.col {
+            if previous.shift().is_none() {
+                input.left = self.col < col;
+                input.right = self.col > col;
+            }
+        } else {
+            input.down = true;
+        }
+        input
+    }
+
+    /// Cells where the first and the second piece of the pair come to rest on a settled
+    /// `grid`: the pair splits and each piece falls to the top of its column. A piece lost
+    /// above a full column gets a row past the top of the grid.
+    pub fn cells(self, grid: &GameGrid) -> [GridPosition; 2] {
+        let first_col = self.col;
+        let second_col = Pair::with_orientation(self.orientation)
+            .get_second_position(GridPosition::new(STARTING_ROW, self.col))
+            .col();
+        let first_row = grid.column_top(first_col);
+        let second_row = grid.column_top(second_col);
+
+        match self.orientation {
+            // the lower piece lands first
+            PairOrientation::ABVertical => [
+                GridPosition::new(second_row + 1, first_col),
+                GridPosition::new(second_row, second_col),
+            ],
+            PairOrientation::BAVertical => [
+                GridPosition::new(first_row, first_col),
+                GridPosition::new(first_row + 1, second_col),
+            ],
+            PairOrientation::ABHorizontal | PairOrientation::BAHorizontal => [
+                GridPosition::new(first_row, first_col),
+                GridPosition::new(second_row, second_col),
+            ],
+        }
+    }
+
+    /// Columns the pieces of a pair of `colors` fall into, the lower piece first.
+    pub fn pieces(self, colors: [PieceColor; 2]) -> [(isize, PieceColor); 2] {
+        let first = GridPosition::new(STARTING_ROW, self.col);
+        let second = Pair::with_orientation(self.orientation).get_second_position(first);
+        if second.row() < first.row() {
+            [(second.col(), colors[1]), (first.col(), colors[0])]
+        } else {
+            [(first.col(), colors[0]), (second.col(), colors[1])]
+        }
+    }
+
+    /// Drops a pair of `colors` on a settled `grid` the way a board does. Returns false
+    /// when a piece is lost above a full column.
+    pub fn drop(self, grid: &mut GameGrid, colors: [PieceColor; 2]) -> bool {
+        let mut fits = true;
+        for (position, color) in self.cells(grid).into_iter().zip(colors) {
+            if grid.is_valid(position) {
+                grid[position] = Some(color);
+            } else {
+                fits = false;
+            }
+        }
+        fits
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::{
+        bag::Bag,
+        board::{GRID_HEIGHT, GRID_WIDTH},
+        nuisance::NuisanceQueue,
+    };
+
+    fn grid() -> GameGrid {
+        Board::empty_grid()
+    }
+
+    #[test]
+    fn test_reachable() {
+        let mut grid = grid();
+        // vertical pairs fit in every column, horizontal ones in one less
+        assert!(Placement::reachable(&grid).len() == 4 * GRID_WIDTH - 2);
+
+        // a wall at the spawn row cuts off the columns behind it
+        for row in 0..GRID_HEIGHT as isize {
+            grid[[row, 2]] = Some(PieceColor::Nuisance);
+        }
+        let placements = Placement::reachable(&grid);
+        assert!(placements.iter().all(|placement| placement.col > 2));
+        let at_col_3 = |orientation| placements.contains(&Placement { col: 3, orientation });
+        assert!(at_col_3(PairOrientation::BAVertical));
+        assert!(!at_col_3(PairOrientation::BAHorizontal));
+    }
+
+    fn board() -> Board {
+        Board::new(Bag::new(0, 4), NuisanceQueue::new(0))
+    }
+
+    /// Plays the inputs of `m` on `board` and checks that the pair comes to rest where the
+    /// move says.
+    fn play(mut board: Board, m: &Move) {
+        let colors = board.pair.as_ref().unwrap().colors;
+        for &input in &m.inputs {
+            board.step(input);
+        }
+        while !board.falling.is_empty() {
+            board.step(PlayerInput::default());
+        }
+        for (cell, color) in m.cells.into_iter().zip(colors) {
+            assert!(board.grid[cell] == Some(color));
+        }
+    }
+
+    #[test]
+    fn test_moves() {
+        let mut board = board();
+        for row in 0..12 {
+            board.grid[[row, 3]] = Some(PieceColor::Nuisance);
+        }
+        for row in 0..GRID_HEIGHT as isize {
+            board.grid[[row, 7]] = Some(PieceColor::Nuisance);
+        }
+
+        let moves = moves(&board);
+        // the full column cuts off the columns behind it, the other one is low enough
+        assert!(moves.iter().all(|m| m.placement.col < 7));
+        assert!(moves.iter().filter(|m| m.placement.col == 3).count() == 4);
+        assert!(!moves.iter().any(|m| m.placement.col == 6
+            && m.placement.orientation == PairOrientation::ABHorizontal));
+
+        for m in &moves {
+            play(board.clone(), m);
+        }
+        let placements: Vec<_> = moves.iter().map(|m| m.placement).collect();
+        assert!(Placement::reachable(&board.grid).iter().all(|p| placements.contains(p)));
+    }
+
+    #[test]
+    fn test_moves_turn_after_shift() {
+        // a vertical pair can't get past column 6, a horizontal one still slides over it
+        let mut board = board();
+        for row in 0..=STARTING_ROW - 1 {
+            board.grid[[row, 6]] = Some(PieceColor::Nuisance);
+        }
+
+        let placement = Placement { col: 8, orientation: PairOrientation::ABVertical };
+        assert!(!Placement::reachable(&board.grid).contains(&placement));
+
+        let moves = moves(&board);
+        let m = moves.iter().find(|m| m.placement == placement).unwrap();
+        assert!(m.inputs.iter().any(|input| input.right));
+        play(board.clone(), m);
+    }
+
+    #[test]
+    fn test_drop() {
+        let mut grid = grid();
+        grid[[0, 1]] = Some(PieceColor::Red);
+
+        let placement = Placement { col: 0, orientation: PairOrientation::ABHorizontal };
+        assert!(placement.drop(&mut grid, [PieceColor::Blue, PieceColor::Green]));
+        assert!(grid[[0, 0]] == Some(PieceColor::Blue));
+        assert!(grid[[1, 1]] == Some(PieceColor::Green));
+
+        let placement = Placement { col: 0, orientation: PairOrientation::ABVertical };
+        assert!(placement.drop(&mut grid, [PieceColor::Yellow, PieceColor::Cyan]));
+        assert!(grid[[1, 0]] == Some(PieceColor::Cyan));
+        assert!(grid[[2, 0]] == Some(PieceColor::Yellow));
+
+        // a column full up to the top of the field still takes one piece in the hidden row
+        for row in 0..GRID_HEIGHT as isize {
+            grid[[row, 5]] = Some(PieceColor::Nuisance);
+        }
+        let placement = Placement { col: 5, orientation: PairOrientation::ABVertical };
+        assert!(!placement.drop(&mut grid, [PieceColor::Red, PieceColor::Blue]));
+        assert!(grid[[GRID_HEIGHT as isize, 5]] == Some(PieceColor::Blue));
+    }
+}
@@ -0,0 +1,365 @@
+use std::{fmt, fs, path::Path, str::FromStr};
+
+use crate::sim::{
+    bag::{MAX_COLORS, MIN_COLORS},
+    game::{Game, Settings, MAX_PLAYERS},
+    input::PlayerInput,
+};
+
+/// Bumped whenever the file format or the rules change in a way that breaks old replays.
+pub const REPLAY_VERSION: u32 = 6;
+
+const HEADER: &str = "puyo_clone replay";
+
+const BUTTONS: [&str; 6] = ["left", "right", "down", "cw", "ccw", "drop"];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplayEvent {
+    /// The buttons held by a player from this frame on.
+    Input { player: usize, input: PlayerInput },
+    /// Every board was reset before this frame.
+    Restart,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReplayEntry {
+    pub frame: u64,
+    pub event: ReplayEvent,
+}
+
+/// The settings of a game and every change in the players' inputs, which is all it takes
+/// to play the game again exactly.
+#[derive(Clone, Debug)]
+pub struct Replay {
+    pub settings: Settings,
+    pub entries: Vec<ReplayEntry>,
+    /// Inputs of the last frame recorded, only inputs that change are written.
+    held: Vec<PlayerInput>,
+}
+
+impl Replay {
+    pub fn new(settings: Settings) -> Self {
+        let held = vec![PlayerInput::default(); settings.players];
+        Self {
+            settings,
+            entries: vec![],
+            held,
+        }
+    }
+
+    /// Records the inputs consumed by `Game::step` on `frame`.
+    pub fn record_inputs(&mut self, frame: u64, inputs: &[PlayerInput]) {
+        for (player, (held, &input)) in self.held.iter_mut().zip(inputs).enumerate() {
+            if *held != input {
+                *held = input;
+                self.entries.push(ReplayEntry {
+                    frame,
+                    event: ReplayEvent::Input { player, input },
+                });
+            }
+        }
+    }
+
+    pub fn record_restart(&mut self, frame: u64) {
+        self.entries.push(ReplayEntry {
+            frame,
+            event: ReplayEvent::Restart,
+        });
+    }
+
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), String> {
+        fs::write(path.as_ref(), self.to_string())
+            .map_err(|error| format!("could not write `{}`: {error}", path.as_ref().display()))
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, String> {
+        fs::read_to_string(path.as_ref())
+            .map_err(|error| format!("could not read `{}`: {error}", path.as_ref().display()))?
+            .parse()
+    }
+}
+
+fn format_input(input: PlayerInput) -> String {
+    let held = [
+        input.left,
+        input.right,
+        input.down,
+        input.rotate_clockwise,
+        input.rotate_counter_clockwise,
+        input.hard_drop,
+    ];
+    let buttons: Vec<&str> = BUTTONS
+        .iter()
+        .zip(held)
+        .filter_map(|(&name, held)| held.then_some(name))
+        .collect();
+
+    if buttons.is_empty() {
+        "none".to_string()
+    } else {
+        buttons.join(",")
+    }
+}
+
+fn parse_input(value: &str) -> Result<PlayerInput, String> {
+    let mut input = PlayerInput::default();
+    if value == "none" {
+        return Ok(input);
+    }
+
+    for button in value.split(',') {
+        match button {
+            "left" => input.left = true,
+            "right" => input.right = true,
+            "down" => input.down = true,
+            "cw" => input.rotate_clockwise = true,
+            "ccw" => input.rotate_counter_clockwise = true,
+            "drop" => input.hard_drop = true,
+            _ => return Err(format!("unknown button `{button}`")),
+        }
+    }
+    Ok(input)
+}
+
+impl fmt::Display for Replay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "{HEADER} {REPLAY_VERSION}")?;
+        writeln!(f, "seed {}", self.settings.seed)?;
+        writeln!(f, "classic {}", self.settings.classic_sequence)?;
+        writeln!(f, "colors {}", self.settings.n_colors)?;
+        writeln!(f, "players {}", self.settings.players)?;
+        writeln!(f, "lock_delay {}", self.settings.lock_delay)?;
+
+        for entry in &self.entries {
+            match entry.event {
+                ReplayEvent::Input { player, input } => {
+                    writeln!(f, "{} {} {}", entry.frame, player, format_input(input))?
+                }
+                ReplayEvent::Restart => writeln!(f, "{} restart", entry.frame)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Value of the `name value` line of the header.
+fn header_value<T: FromStr>(line: Option<&str>, name: &str) -> Result<T, String> {
+    line.and_then(|line| line.strip_prefix(name))
+        .and_then(|value| value.trim().parse().ok())
+        .ok_or(format!("missing or invalid `{name}`"))
+}
+
+impl FromStr for Replay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, String> {
+        let mut lines = s.lines();
+
+        let version: u32 = header_value(lines.next(), HEADER).map_err(|_| "not a replay file")?;
+        if version != REPLAY_VERSION {
+            return Err(format!(
+                "replay version {version} is not supported, expected {REPLAY_VERSION}"
+            ));
+        }
+
+        let settings = Settings {
+            seed: header_value(lines.next(), "seed")?,
+            classic_sequence: header_value(lines.next(), "classic")?,
+            n_colors: header_value(lines.next(), "colors")?,
+            players: header_value(lines.next(), "players")?,
+            lock_delay: header_value(lines.next(), "lock_delay")?,
+        };
+        if !(MIN_COLORS..=MAX_COLORS).contains(&settings.n_colors) {
+            return Err(format!("`colors` must be between {MIN_COLORS} and {MAX_COLORS}"));
+        }
+        if !(1..=MAX_PLAYERS).contains(&settings.players) {
+            return Err(format!("`players` must be between 1 and {MAX_PLAYERS}"));
+        }
+        let mut replay = Replay::new(settings);
+
+        for line in lines.filter(|line| !line.trim().is_empty()) {
+            let invalid = || format!("invalid replay line `{line}`");
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let frame = fields[0].parse().map_err(|_| invalid())?;
+
+            let event = match fields[1..] {
+                ["restart"] => ReplayEvent::Restart,
+                [player, input] => {
+                    let player = player
+                        .parse()
+                        .ok()
+                        .filter(|&player| player < replay.settings.players)
+                        .ok_or_else(invalid)?;
+                    let input = parse_input(input).map_err(|error| format!("{}: {error}", invalid()))?;
+                    ReplayEvent::Input { player, input }
+                }
+                _ => return Err(invalid()),
+            };
+            replay.entries.push(ReplayEntry { frame, event });
+        }
+
+        Ok(replay)
+    }
+}
+
+/// Plays the entries of a replay back, one frame at a time.
+pub struct Playback {
+    replay: Replay,
+    next: usize,
+    inputs: Vec<PlayerInput>,
+}
+
+impl Playback {
+    pub fn new(replay: Replay) -> Self {
+        let inputs = vec![PlayerInput::default(); replay.settings.players];
+        Self {
+            replay,
+            next: 0,
+            inputs,
+        }
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.replay.settings
+    }
+
+    /// Whether the game restarts before `frame`, only true once per restart.
+    pub fn take_restart(&mut self, frame: u64) -> bool {
+        match self.replay.entries.get(self.next) {
+            Some(&ReplayEntry { frame: entry_frame, event: ReplayEvent::Restart })
+                if entry_frame <= frame =>
+            {
+                self.next += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Inputs of every player on `frame`. Stops at the next restart, which has to be taken
+    /// with `take_restart` first.
+    pub fn inputs(&mut self, frame: u64) -> &[PlayerInput] {
+        while let Some(entry) = self.replay.entries.get(self.next) {
+            match entry.event {
+                ReplayEvent::Input { player, input } if entry.frame <= frame => {
+                    self.inputs[player] = input;
+                    self.next += 1;
+                }
+                _ => break,
+            }
+        }
+        &self.inputs
+    }
+
+    /// Inputs of the next frame of `game`, which is reset first when the replay restarted
+    /// there. The inputs still held at the end of the replay stay held, the game goes on
+    /// until it is over like it did when it was recorded.
+    pub fn next_inputs(&mut self, game: &mut Game) -> Vec<PlayerInput> {
+        if self.take_restart(game.frame) {
+            game.reset();
+        }
+        self.inputs(game.frame).to_vec()
+    }
+
+    pub fn is_finished(&self) -> bool {
+        self.next >= self.replay.entries.len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> Settings {
+        Settings {
+            seed: 9,
+            classic_sequence: true,
+            n_colors: 5,
+            players: 2,
+            ..Default::default()
+        }
+    }
+
+    fn input(frame: u64, player: usize) -> PlayerInput {
+        let frame = frame + 37 * player as u64;
+        PlayerInput {
+            left: frame % 80 < 10,
+            right: frame % 110 < 25,
+            down: frame % 40 < 20,
+            rotate_clockwise: frame % 13 < 2,
+            rotate_counter_clockwise: frame % 29 < 3,
+            hard_drop: frame % 170 < 2,
+        }
+    }
+
+    #[test]
+    fn test_format() {
+        let mut replay = Replay::new(settings());
+        replay.record_inputs(0, &[input(0, 0), input(0, 1)]);
+        replay.record_inputs(1, &[input(0, 0), PlayerInput::default()]);
+        replay.record_restart(2);
+
+        let text = replay.to_string();
+        assert!(text.starts_with("puyo_clone replay 6\nseed 9\n"));
+        let parsed: Replay = text.parse().unwrap();
+        assert!(parsed.settings == replay.settings);
+        assert!(parsed.entries == replay.entries);
+
+        assert!("puyo_clone replay 5\n".parse::<Replay>().is_err());
+        assert!(format!("{text}3 2 none\n").parse::<Replay>().is_err());
+        assert!(format!("{text}3 0 up\n").parse::<Replay>().is_err());
+        assert!(text.replace("colors 5", "colors 7").parse::<Replay>().is_err());
+        assert!(text.replace("players 2", "players 0").parse::<Replay>().is_err());
+    }
+
+    #[test]
+    fn test_playback() {
+        let mut game = Game::new(&settings());
+        let mut replay = Replay::new(settings());
+        for frame in 0..3000 {
+            if frame == 1500 {
+                replay.record_restart(game.frame);
+                game.reset();
+            }
+            let inputs = [input(frame, 0), input(frame, 1)];
+            replay.record_inputs(game.frame, &inputs);
+            game.step(&inputs);
+        }
+
+        let replay: Replay = replay.to_string().parse().unwrap();
+        let mut played = Game::new(&replay.settings);
+        let mut playback = Playback::new(replay);
+        while !playback.is_finished() || played.frame < game.frame {
+            let inputs = playback.next_inputs(&mut played);
+            played.step(&inputs);
+        }
+
+        for (board, played_board) in game.boards.iter().zip(&played.boards) {
+            assert!(board.grid == played_board.grid);
+            assert!(board.score.value == played_board.score.value);
+        }
+    }
+
+    #[test]
+    fn test_playback_held_inputs() {
+        // the buttons pressed on the first frame stay held until the game is over
+        let settings = Settings { players: 1, ..settings() };
+        let held = PlayerInput { left: true, down: true, ..Default::default() };
+        let mut game = Game::new(&settings);
+        let mut replay = Replay::new(settings.clone());
+        while game.outcome().is_none() {
+            replay.record_inputs(game.frame, &[held]);
+            game.step(&[held]);
+        }
+
+        let mut played = Game::new(&settings);
+        let mut playback = Playback::new(replay);
+        while played.outcome().is_none() {
+            let inputs = playback.next_inputs(&mut played);
+            played.step(&inputs);
+        }
+        assert!(playback.is_finished());
+        assert!(played.frame == game.frame);
+        assert!(played.boards[0].grid == game.boards[0].grid);
+    }
+}
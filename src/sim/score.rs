@@ -0,0 +1,97 @@
+use crate::sim::piece::PieceColor;
+
+const MAX_MULTIPLIER: u32 = 999;
+/// Points for a chain that empties the whole grid, on top of its links.
+pub const ALL_CLEAR_BONUS: u64 = 2100;
+
+const CHAIN_POWER: [u32; 8] = [0, 8, 16, 32, 64, 96, 128, 160];
+const CHAIN_POWER_STEP: u32 = 32;
+const COLOR_BONUS: [u32; 5] = [0, 3, 6, 12, 24];
+const GROUP_BONUS: [u32; 8] = [0, 2, 3, 4, 5, 6, 7, 10];
+
+#[derive(Clone, Default)]
+pub struct Score {
+    pub value: u64,
+    pub chain: u32,
+}
+
+fn chain_power(chain: u32) -> u32 {
+    let index = chain.saturating_sub(1) as usize;
+    match CHAIN_POWER.get(index) {
+        Some(&power) => power,
+        None => {
+            let last = CHAIN_POWER.len() - 1;
+            CHAIN_POWER[last] + CHAIN_POWER_STEP * (index - last) as u32
+        }
+    }
+}
+
+fn color_bonus(n_colors: usize) -> u32 {
+    COLOR_BONUS[n_colors.saturating_sub(1).min(COLOR_BONUS.len() - 1)]
+}
+
+fn group_bonus(size: usize) -> u32 {
+    GROUP_BONUS[size.saturating_sub(4).min(GROUP_BONUS.len() - 1)]
+}
+
+/// Score of a single chain link, given the color and size of every group popped in it.
+pub fn link_score(chain: u32, groups: &[(PieceColor, usize)]) -> u64 {
+    let popped: usize = groups.iter().map(|(_, size)| size).sum();
+
+    let mut colors: Vec<PieceColor> = groups.iter().map(|(color, _)| *color).collect();
+    colors.sort_by_key(|color| *color as u8);
+    colors.dedup();
+
+    let multiplier = chain_power(chain)
+        + color_bonus(colors.len())
+        + groups.iter().map(|(_, size)| group_bonus(*size)).sum::<u32>();
+    let multiplier = multiplier.clamp(1, MAX_MULTIPLIER);
+
+    10 * popped as u64 * multiplier as u64
+}
+
+impl Score {
+    pub fn add_link(&mut self, groups: &[(PieceColor, usize)]) -> u64 {
+        self.chain += 1;
+        let score = link_score(self.chain, groups);
+        self.value += score;
+        score
+    }
+
+    pub fn add_all_clear(&mut self) {
+        self.value += ALL_CLEAR_BONUS;
+    }
+
+    pub fn reset_chain(&mut self) {
+        self.chain = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_single_group() {
+        // 4 pieces, no chain power, no color or group bonus: multiplier is clamped to 1
+        assert!(link_score(1, &[(PieceColor::Red, 4)]) == 40);
+    }
+
+    #[test]
+    fn test_chain_power() {
+        assert!(link_score(2, &[(PieceColor::Red, 4)]) == 40 * 8);
+        assert!(link_score(5, &[(PieceColor::Blue, 4)]) == 40 * 64);
+        assert!(link_score(10, &[(PieceColor::Blue, 4)]) == 40 * 224);
+    }
+
+    #[test]
+    fn test_color_and_group_bonus() {
+        let groups = [(PieceColor::Red, 5), (PieceColor::Green, 4)];
+        assert!(link_score(1, &groups) == 90 * (3 + 2));
+    }
+
+    #[test]
+    fn test_multiplier_cap() {
+        assert!(link_score(40, &[(PieceColor::Red, 4)]) == 40 * 999);
+    }
+}
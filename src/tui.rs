@@ -0,0 +1,486 @@
+//! Plays the game in a terminal, for machines without a display. The boards are drawn with
+//! colored characters and the keys are read from the terminal.
+//!
+//! Most terminals only report key presses, repeated while a key is held, so a button counts
+//! as held for a few frames after each press. Terminals that also report key releases get
+//! buttons held for exactly as long as their keys.
+use std::{
+    io::{self, Write},
+    time::{Duration, Instant},
+};
+
+use crossterm::{
+    cursor,
+    event::{
+        self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers, KeyboardEnhancementFlags,
+        PopKeyboardEnhancementFlags, PushKeyboardEnhancementFlags,
+    },
+    execute, queue,
+    style::{Color, Print, Stylize},
+    terminal::{self, ClearType},
+};
+
+use crate::{
+    cpu::{Cpu, Level},
+    sim::{
+        board::Board,
+        game::{Game, Outcome, Settings, FRAME_RATE},
+        grid::GridPosition,
+        input::PlayerInput,
+        piece::PieceColor,
+        replay::Replay,
+    },
+};
+
+/// Frames a button stays held after its key was pressed, long enough to bridge the gaps
+/// between the repeated presses of a held key.
+const HOLD_FRAMES: u32 = 4;
+/// Columns between two boards in versus.
+const BOARD_SPACING: usize = 4;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Button {
+    Left,
+    Right,
+    Down,
+    RotateClockwise,
+    RotateCounterClockwise,
+    HardDrop,
+}
+
+const BUTTONS: [Button; 6] = [
+    Button::Left,
+    Button::Right,
+    Button::Down,
+    Button::RotateClockwise,
+    Button::RotateCounterClockwise,
+    Button::HardDrop,
+];
+
+/// Keys of every human player, the default ones of the window. Two players share the
+/// keyboard in versus. The bindings file of the window names bevy keys and isn't read here,
+/// so rebinding only changes the keys of the window.
+fn bindings(humans: usize) -> Vec<(KeyCode, usize, Button)> {
+    use Button::*;
+    use KeyCode::{Char, Down as DownKey, Left as LeftKey, Right as RightKey, Up as UpKey};
+
+    if humans == 1 {
+        return vec![
+            (LeftKey, 0, Left),
+            (RightKey, 0, Right),
+            (DownKey, 0, Down),
+            (Char('d'), 0, RotateClockwise),
+            (Char('a'), 0, RotateCounterClockwise),
+            (UpKey, 0, HardDrop),
+        ];
+    }
+    vec![
+        (Char('a'), 0, Left),
+        (Char('d'), 0, Right),
+        (Char('s'), 0, Down),
+        (Char('g'), 0, RotateClockwise),
+        (Char('f'), 0, RotateCounterClockwise),
+        (Char('w'), 0, HardDrop),
+        (LeftKey, 1, Left),
+        (RightKey, 1, Right),
+        (DownKey, 1, Down),
+        (Char('.'), 1, RotateClockwise),
+        (Char(','), 1, RotateCounterClockwise),
+        (UpKey, 1, HardDrop),
+    ]
+}
+
+/// The buttons `code` is bound to, with their players.
+fn bound(
+    bindings: &[(KeyCode, usize, Button)],
+    code: KeyCode,
+) -> impl Iterator<Item = (usize, Button)> + '_ {
+    bindings
+        .iter()
+        .filter(move |binding| binding.0 == code)
+        .map(|&(_, player, button)| (player, button))
+}
+
+/// Frames left during which each button of a player is held.
+#[derive(Clone, Copy, Default)]
+struct HeldButtons([u32; BUTTONS.len()]);
+
+impl HeldButtons {
+    fn input(&self) -> PlayerInput {
+        let held = |button: Button| self.0[button as usize] > 0;
+        PlayerInput {
+            left: held(Button::Left),
+            right: held(Button::Right),
+            down: held(Button::Down),
+            rotate_clockwise: held(Button::RotateClockwise),
+            rotate_counter_clockwise: held(Button::RotateCounterClockwise),
+            hard_drop: held(Button::HardDrop),
+        }
+    }
+
+    /// Turns, drops and shifts happen when a button is pressed, a turn or a drop pressed
+    /// again while its button is still held has to let go of it first.
+    fn press(&mut self, button: Button, frames: u32) {
+        let once = matches!(
+            button,
+            Button::RotateClockwise | Button::RotateCounterClockwise | Button::HardDrop
+        );
+        self.0[button as usize] = if once { 1 } else { frames };
+    }
+
+    fn release(&mut self, button: Button) {
+        self.0[button as usize] = 0;
+    }
+
+    fn tick(&mut self) {
+        for frames in self.0.iter_mut() {
+            if *frames != u32::MAX {
+                *frames = frames.saturating_sub(1);
+            }
+        }
+    }
+}
+
+/// Letter of a color in `board_text`.
+fn letter(color: PieceColor) -> char {
+    match color {
+        PieceColor::Red => 'R',
+        PieceColor::Blue => 'B',
+        PieceColor::Purple => 'P',
+        PieceColor::Green => 'G',
+        PieceColor::Yellow => 'Y',
+        PieceColor::Cyan => 'C',
+        PieceColor::Nuisance => 'N',
+    }
+}
+
+fn terminal_color(color: PieceColor) -> Color {
+    match color {
+        PieceColor::Red => Color::Red,
+        PieceColor::Blue => Color::Blue,
+        PieceColor::Purple => Color::Magenta,
+        PieceColor::Green => Color::Green,
+        PieceColor::Yellow => Color::Yellow,
+        PieceColor::Cyan => Color::Cyan,
+        PieceColor::Nuisance => Color::Grey,
+    }
+}
+
+/// What every cell of the visible field shows, rows from the bottom: the grid, the pair and
+/// the pieces falling on their own.
+fn cells(board: &Board) -> Vec<Vec<Option<PieceColor>>> {
+    let mut cells: Vec<Vec<Option<PieceColor>>> = (0..board.grid.visible_height as isize)
+        .map(|row| (0..board.grid.width as isize).map(|col| board.grid[[row, col]]).collect())
+        .collect();
+
+    if board.popping_hidden() {
+        for position in &board.popping {
+            cells[position.row() as usize][position.col() as usize] = None;
+        }
+    }
+
+    let pair_pieces = board.pair.iter().flat_map(|pair| pair.pieces());
+    for piece in pair_pieces.chain(board.falling.iter().copied()) {
+        let position: GridPosition = piece.position();
+        if board.grid.is_valid(position) && !board.grid.is_hidden(position) {
+            cells[position.row() as usize][position.col() as usize] = Some(piece.color);
+        }
+    }
+    cells
+}
+
+/// The board as plain text, top row first, one letter per piece and `.` for empty cells.
+/// Handy to paste a board state in a bug report.
+pub fn board_text(board: &Board) -> String {
+    cells(board)
+        .iter()
+        .rev()
+        .map(|row| {
+            let mut line: String = row.iter().map(|cell| cell.map_or('.', letter)).collect();
+            line.push('\n');
+            line
+        })
+        .collect()
+}
+
+/// Puts the terminal in raw mode on an alternate screen, and back when dropped.
+struct Screen {
+    stdout: io::Stdout,
+    /// Whether the terminal reports key releases.
+    releases: bool,
+    /// Lines drawn last time, only the ones that change are drawn again.
+    lines: Vec<String>,
+}
+
+impl Screen {
+    fn enter() -> io::Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        execute!(stdout, terminal::EnterAlternateScreen, cursor::Hide)?;
+
+        let releases = terminal::supports_keyboard_enhancement().unwrap_or(false);
+        if releases {
+            let flags = KeyboardEnhancementFlags::REPORT_EVENT_TYPES;
+            execute!(stdout, PushKeyboardEnhancementFlags(flags))?;
+        }
+        Ok(Self {
+            stdout,
+            releases,
+            lines: vec![],
+        })
+    }
+
+    fn draw(&mut self, lines: Vec<String>) -> io::Result<()> {
+        if lines.len() != self.lines.len() {
+            queue!(self.stdout, terminal::Clear(ClearType::All))?;
+            self.lines.clear();
+        }
+        for (row, line) in lines.iter().enumerate() {
+            if self.lines.get(row) != Some(line) {
+                queue!(
+                    self.stdout,
+                    cursor::MoveTo(0, row as u16),
+                    terminal::Clear(ClearType::CurrentLine),
+                    Print(line)
+                )?;
+            }
+        }
+        self.lines = lines;
+        self.stdout.flush()
+    }
+
+    /// Draws everything again, after the terminal was resized.
+    fn invalidate(&mut self) {
+        self.lines.clear();
+    }
+}
+
+impl Drop for Screen {
+    fn drop(&mut self) {
+        if self.releases {
+            let _ = execute!(self.stdout, PopKeyboardEnhancementFlags);
+        }
+        let _ = execute!(self.stdout, cursor::Show, terminal::LeaveAlternateScreen);
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// The lines of one board: the grid with a border, the next pairs on its right, then the
+/// score, the nuisance about to fall and the all clear banner. All lines are as wide, so
+/// that boards side by side line up.
+fn board_lines(board: &Board, title: &str) -> Vec<String> {
+    let empty = " .".dark_grey().to_string();
+    let preview: Vec<[PieceColor; 2]> = board.bag.preview().copied().collect();
+    let preview_cell = |row: usize| {
+        // every pair takes two rows and a blank one, first piece on top
+        let pair = preview.get(row / 3)?;
+        pair.get(row % 3).copied()
+    };
+
+    let mut lines = vec![format!(" {title:<19} next")];
+    for (row, cells) in cells(board).iter().rev().enumerate() {
+        let mut line = "│".to_string();
+        for cell in cells {
+            match cell {
+                Some(color) => line += &"  ".on(terminal_color(*color)).to_string(),
+                None => line += &empty,
+            }
+        }
+        line += "│ ";
+        line += &match preview_cell(row) {
+            Some(color) => "  ".on(terminal_color(color)).to_string(),
+            None => "  ".to_string(),
+        };
+        lines.push(line);
+    }
+    lines.push(format!("└{}┘   ", "──".repeat(board.grid.width)));
+    lines.push(format!(" score {:<18}", board.score.value));
+    lines.push(format!(" nuisance {:<15}", board.nuisance.pending));
+    let banner = if board.all_clear { "ALL CLEAR" } else { "" };
+    lines.push(format!(" {}", format!("{banner:<24}").bold()));
+    lines
+}
+
+/// Every board side by side, then a status line.
+fn screen_lines(game: &Game, cpu: bool, status: &str) -> Vec<String> {
+    let boards: Vec<Vec<String>> = game
+        .boards
+        .iter()
+        .enumerate()
+        .map(|(player, board)| {
+            let title = match (game.boards.len(), cpu) {
+                (1, _) => String::new(),
+                (_, true) if player == 1 => "CPU".to_string(),
+                _ => format!("Player {}", player + 1),
+            };
+            board_lines(board, &title)
+        })
+        .collect();
+
+    let spacing = " ".repeat(BOARD_SPACING);
+    let mut lines: Vec<String> = (0..boards[0].len())
+        .map(|row| {
+            let row: Vec<&str> = boards.iter().map(|lines| lines[row].as_str()).collect();
+            row.join(&spacing)
+        })
+        .collect();
+    lines.push(String::new());
+    lines.push(status.to_string());
+    lines
+}
+
+fn status(game: &Game, cpu: bool, paused: bool) -> String {
+    match game.outcome() {
+        Some(_) if game.boards.len() == 1 => "Game over - r: restart, q: quit".to_string(),
+        Some(Outcome::Draw) => "Draw - r: restart, q: quit".to_string(),
+        Some(Outcome::Loser(loser)) => {
+            let winner = if cpu && loser == 0 {
+                "CPU wins".to_string()
+            } else {
+                format!("Player {} wins", 2 - loser)
+            };
+            format!("{winner} - r: restart, q: quit")
+        }
+        None if paused => "Paused - p: resume, r: restart, q: quit".to_string(),
+        None => "p: pause, q: quit".to_string(),
+    }
+}
+
+fn terminal_error(error: io::Error) -> String {
+    format!("terminal error: {error}")
+}
+
+/// Plays a game in the terminal until the player quits, the second board played by the
+/// computer at `cpu` level if given. Returns the replay of the game and prints the final
+/// boards as text once the terminal is back to normal.
+pub fn run(settings: &Settings, cpu: Option<Level>) -> Result<Replay, String> {
+    let mut game = Game::new(settings);
+    let mut replay = Replay::new(settings.clone());
+    let mut cpu = cpu.filter(|_| settings.players > 1).map(Cpu::new);
+    let humans = if cpu.is_some() { 1 } else { settings.players };
+    let bindings = bindings(humans);
+    let mut held = vec![HeldButtons::default(); settings.players];
+    let mut paused = false;
+
+    let mut screen = Screen::enter().map_err(terminal_error)?;
+    let press_frames = if screen.releases { u32::MAX } else { HOLD_FRAMES };
+    let frame_time = Duration::from_secs_f64(1. / FRAME_RATE);
+    let mut next_frame = Instant::now();
+
+    'game: loop {
+        // read the keys until it's time for the next frame
+        while let Some(timeout) = next_frame.checked_duration_since(Instant::now()) {
+            if !event::poll(timeout).map_err(terminal_error)? {
+                break;
+            }
+            let key = match event::read().map_err(terminal_error)? {
+                Event::Key(key) => key,
+                Event::Resize(_, _) => {
+                    screen.invalidate();
+                    continue;
+                }
+                _ => continue,
+            };
+
+            let KeyEvent { code, modifiers, kind, .. } = key;
+            let code = match code {
+                KeyCode::Char(c) => KeyCode::Char(c.to_ascii_lowercase()),
+                code => code,
+            };
+            if kind == KeyEventKind::Release {
+                for (player, button) in bound(&bindings, code) {
+                    held[player].release(button);
+                }
+                continue;
+            }
+
+            match code {
+                KeyCode::Char('c') if modifiers.contains(KeyModifiers::CONTROL) => break 'game,
+                KeyCode::Char('q') => break 'game,
+                KeyCode::Char('p') | KeyCode::Esc if game.outcome().is_none() => paused = !paused,
+                KeyCode::Char('r') if paused || game.outcome().is_some() => {
+                    game.reset();
+                    replay.record_restart(game.frame);
+                    if let Some(cpu) = cpu.as_mut() {
+                        cpu.reset();
+                    }
+                    paused = false;
+                }
+                // a repeated key only keeps moving the pair, it never turns it again
+                _ if kind == KeyEventKind::Repeat && screen.releases => {}
+                code => {
+                    for (player, button) in bound(&bindings, code) {
+                        held[player].press(button, press_frames);
+                    }
+                }
+            }
+        }
+        next_frame += frame_time;
+
+        if !paused && game.outcome().is_none() {
+            let mut inputs: Vec<PlayerInput> = held.iter().map(HeldButtons::input).collect();
+            if let Some(cpu) = cpu.as_mut() {
+                inputs[1] = cpu.input(&game.boards[1]);
+            }
+            replay.record_inputs(game.frame, &inputs);
+            let events = game.step(&inputs);
+            if let Some(cpu) = cpu.as_mut() {
+                cpu.update(&events[1]);
+            }
+            for held in held.iter_mut() {
+                held.tick();
+            }
+        }
+
+        let status = status(&game, cpu.is_some(), paused);
+        screen.draw(screen_lines(&game, cpu.is_some(), &status)).map_err(terminal_error)?;
+    }
+
+    drop(screen);
+    for (player, board) in game.boards.iter().enumerate() {
+        println!("Player {}, score {}", player + 1, board.score.value);
+        print!("{}", board_text(board));
+    }
+    Ok(replay)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim::board::{GRID_HEIGHT, GRID_WIDTH, STARTING_COL, STARTING_ROW};
+
+    #[test]
+    fn test_board_text() {
+        let mut game = Game::new(&Settings { seed: 1, ..Default::default() });
+        let board = &mut game.boards[0];
+        board.grid[[0, 0]] = Some(PieceColor::Red);
+        board.grid[[0, 1]] = Some(PieceColor::Nuisance);
+
+        let text = board_text(board);
+        let lines: Vec<&str> = text.lines().collect();
+        assert!(lines.len() == GRID_HEIGHT);
+        assert!(lines.iter().all(|line| line.len() == GRID_WIDTH));
+        assert!(lines[GRID_HEIGHT - 1].starts_with("RN."));
+
+        // the pair spawns with its second piece below the first one
+        let colors = board.pair.as_ref().unwrap().colors;
+        let (row, col) = (GRID_HEIGHT - 1 - STARTING_ROW as usize, STARTING_COL as usize);
+        assert!(lines[row].chars().nth(col) == Some(letter(colors[0])));
+        assert!(lines[row + 1].chars().nth(col) == Some(letter(colors[1])));
+    }
+
+    #[test]
+    fn test_all_clear_banner() {
+        let mut game = Game::new(&Settings { seed: 1, ..Default::default() });
+        let board = &mut game.boards[0];
+        let lines = board_lines(board, "");
+        board.all_clear = true;
+        let banner_lines = board_lines(board, "");
+
+        // the banner goes under the field, which is drawn the same
+        assert!(banner_lines.len() == lines.len());
+        let last = lines.len() - 1;
+        assert!(banner_lines[..last] == lines[..last]);
+        assert!(banner_lines[last].contains("ALL CLEAR"));
+    }
+}